@@ -0,0 +1,5 @@
+pub mod models;
+pub mod pipeline;
+
+pub use models::{MatrixClient, MinifluxClient, Model, TelegramClient};
+pub use pipeline::Pipeline;
@@ -1,7 +1,8 @@
-mod models;
-
-use models::{MatrixClient, MinifluxClient, Model, TelegramClient};
-use serde_json::{json, Value};
+use miniflux_client::{
+    models::{backend, AckPolicy, Auth, ContentFetcher, ContentFormat, HttpConfig, IconCache, MinifluxError, Normalizer, RetryPolicy, SummaryCache},
+    pipeline::{BatchSummarizer, ContentEnricher, MinifluxFetcher, PerEntrySummarizer, Summarizer},
+    MatrixClient, MinifluxClient, Model, Pipeline, TelegramClient,
+};
 use std::{env, time};
 use tracing::{debug, error, info};
 use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
@@ -29,11 +30,93 @@ async fn main() {
         .parse::<usize>()
         .unwrap_or(MAX_ENTRIES);
     debug!("Max entries: {}", max_entries);
-    let miniflux = MinifluxClient::new(
-        env::var("MINIFLUX_URL").expect("MINIFLUX_URL is mandatory"),
-        env::var("MINIFLUX_TOKEN").expect("MINIFLUX_TOKEN is mandatory"),
+    let content_fetcher = env::var("FETCH_CONTENT")
+        .map(|value| value == "true")
+        .unwrap_or(false)
+        .then(|| {
+            let default = ContentFetcher::default();
+            ContentFetcher::new(
+                env::var("FETCH_CONTENT_MIN_LENGTH")
+                    .ok()
+                    .and_then(|value| value.parse::<usize>().ok())
+                    .unwrap_or(default.min_length),
+                env::var("FETCH_CONTENT_CONCURRENCY")
+                    .ok()
+                    .and_then(|value| value.parse::<usize>().ok())
+                    .unwrap_or(default.concurrency),
+            )
+        });
+    debug!("Content fetcher: {:?}", content_fetcher);
+    let mut normalizer = Normalizer::new(
+        env::var("CONTENT_FORMAT")
+            .ok()
+            .and_then(|value| value.parse::<ContentFormat>().ok())
+            .unwrap_or(ContentFormat::Markdown),
     );
-    let miniflux_categories = miniflux.get_categories().await;
+    if let Some(max_chars) = env::var("CONTENT_MAX_CHARS").ok().and_then(|value| value.parse::<usize>().ok()) {
+        normalizer = normalizer.with_max_chars(max_chars);
+    } else if let Some(max_tokens) = env::var("CONTENT_MAX_TOKENS").ok().and_then(|value| value.parse::<usize>().ok()) {
+        normalizer = normalizer.with_max_tokens(max_tokens);
+    }
+    debug!("Normalizer: {:?}", normalizer);
+    let attach_media = env::var("ATTACH_MEDIA")
+        .map(|value| value != "false")
+        .unwrap_or(true);
+    debug!("Attach media: {}", attach_media);
+    let per_item = env::var("NOTIFY_PER_ITEM")
+        .map(|value| value == "true")
+        .unwrap_or(false);
+    debug!("Notify per item: {}", per_item);
+    let icon_cache = env::var("ICON_CACHE_DIR").ok().map(IconCache::new);
+    debug!("Icon cache: {:?}", icon_cache);
+    let ack_policy = env::var("ACK_POLICY")
+        .ok()
+        .and_then(|value| value.parse::<AckPolicy>().ok())
+        .unwrap_or_default();
+    debug!("Acknowledge policy: {:?}", ack_policy);
+    let http_config = HttpConfig::from_env();
+    debug!("HTTP config: {:?}", http_config);
+    let http = http_config
+        .build()
+        .expect("Unable to build the HTTP client, check HTTP_PROXY_URL");
+    let retry = RetryPolicy::from_env();
+    debug!("Retry policy: {:?}", retry);
+    let miniflux_auth = match (env::var("MINIFLUX_USERNAME"), env::var("MINIFLUX_PASSWORD")) {
+        (Ok(username), Ok(password)) => Auth::basic(&username, &password),
+        _ => Auth::token(&env::var("MINIFLUX_TOKEN").expect("MINIFLUX_TOKEN or MINIFLUX_USERNAME/MINIFLUX_PASSWORD are mandatory")),
+    };
+    let miniflux = MinifluxClient::with_auth(
+        env::var("MINIFLUX_URL").expect("MINIFLUX_URL is mandatory"),
+        miniflux_auth,
+    )
+    .with_client(http.clone())
+    .with_retry(retry.clone());
+    if let Err(e) = miniflux.healthcheck().await {
+        error!("Miniflux is not reachable, check MINIFLUX_URL: {}", e);
+        std::process::exit(1);
+    }
+    match miniflux.me().await {
+        Ok(user) => info!("Connected to Miniflux as {}", user.username),
+        Err(e) if e.is_unauthorized() => {
+            error!("Miniflux rejected the credentials, check MINIFLUX_TOKEN or MINIFLUX_USERNAME/MINIFLUX_PASSWORD: {}", e);
+            std::process::exit(1);
+        }
+        Err(e) => {
+            error!("Unable to validate the Miniflux configuration: {}", e);
+            std::process::exit(1);
+        }
+    }
+    match miniflux.version().await {
+        Ok(version) => info!("Miniflux version: {}", version.version),
+        Err(e) => debug!("Unable to get the Miniflux version: {}", e),
+    }
+    let miniflux_categories = match miniflux.get_categories().await {
+        Ok(categories) => categories,
+        Err(e) => {
+            error!("Error getting categories from Miniflux: {}", e);
+            std::process::exit(1);
+        }
+    };
     debug!("Miniflux categories: {:?}", miniflux_categories);
     let categories = match env::var("MINIFLUX_CATEGORIES").ok() {
         Some(categories) => categories
@@ -41,235 +124,135 @@ async fn main() {
             .map(|s| {
                 let name = s.trim().to_string().to_lowercase();
                 miniflux_categories
-                    .as_ref()
-                    .unwrap()
                     .iter()
-                    .find(|c| c["title"].as_str().unwrap().to_lowercase() == name)
+                    .find(|c| c.title.to_lowercase() == name)
             })
             .collect::<Vec<_>>()
             .iter()
             .filter(|item| item.is_some())
-            .map(|item| item.unwrap()["id"].as_i64().unwrap())
+            .map(|item| item.unwrap().id)
             .collect::<Vec<_>>(),
         None => Vec::new(),
     };
     debug!("Categories: {:?}", categories);
-    let matrix = MatrixClient::new(
-        env::var("MATRIX_URL").expect("MATRIX_URL is mandatory"),
-        env::var("MATRIX_TOKEN").expect("MATRIX_TOKEN is mandatory"),
-        env::var("MATRIX_ROOM").expect("MATRIX_ROOM is mandatory"),
-    );
-    let telegram = TelegramClient::new(
-        env::var("TELEGRAM_TOKEN").expect("TELEGRAM_TOKEN is mandatory"),
-        env::var("TELEGRAM_CHAT_ID").expect("TELEGRAM_CHAT_ID is mandatory"),
-        env::var("TELEGRAM_THREAD_ID").unwrap_or_else(|_| "0".to_string()),
-    );
+    let matrix = env::var("MATRIX_URL").ok().map(|url| {
+        let matrix = MatrixClient::new(
+            url,
+            env::var("MATRIX_TOKEN").expect("MATRIX_TOKEN is mandatory when MATRIX_URL is set"),
+            env::var("MATRIX_ROOM").expect("MATRIX_ROOM is mandatory when MATRIX_URL is set"),
+        )
+        .with_client(http.clone())
+        .with_retry(retry.clone());
+        match &icon_cache {
+            Some(cache) => matrix.with_icon_cache(cache.clone()),
+            None => matrix,
+        }
+    });
+    let telegram = env::var("TELEGRAM_TOKEN").ok().map(|token| {
+        TelegramClient::new(
+            token,
+            env::var("TELEGRAM_CHAT_ID").expect("TELEGRAM_CHAT_ID is mandatory when TELEGRAM_TOKEN is set"),
+            env::var("TELEGRAM_THREAD_ID").unwrap_or_else(|_| "0".to_string()),
+        )
+        .with_client(http.clone())
+        .with_retry(retry.clone())
+    });
+    if matrix.is_none() && telegram.is_none() {
+        error!("No notifier configured, set MATRIX_URL and/or TELEGRAM_TOKEN");
+        std::process::exit(1);
+    }
+    let model_provider = env::var("MODEL_PROVIDER").unwrap_or_else(|_| "openai".to_string());
+    let model_backend = match backend(&model_provider) {
+        Ok(model_backend) => model_backend,
+        Err(e) => {
+            error!("{}, use openai, anthropic or ollama", e);
+            std::process::exit(1);
+        }
+    };
+    debug!("Model provider: {:?}", model_backend);
     let model = Model::new(
         std::env::var("MODEL_URL").expect("MODEL_URL is mandatory"),
-        std::env::var("MODEL_API_KEY").expect("MODEL_API_KEY is mandatory"),
+        // Not needed by a local Ollama
+        std::env::var("MODEL_API_KEY").unwrap_or_default(),
         std::env::var("MODEL_NAME").expect("MODEL_NAME is mandatory"),
         std::env::var("MODEL_DESCRIPTION").expect("MODEL_DESCRIPTION is mandatory"),
         std::env::var("MODEL_PROMPT").expect("MODEL_PROMPT is mandatory"),
-    );
-    loop {
-        if let Err(e) = miniflux.refresh_all_feeds().await {
-            error!("Error refreshing Miniflux feeds: {}", e);
-        }
-        let entries = if categories.is_empty() {
-            match miniflux.get_entries(max_entries).await {
-                Ok(entries) => {
-                    debug!("Entries: {:?}", entries);
-                    entries
-                }
-                Err(e) => {
-                    error!("Error getting entries from Miniflux: {}", e);
-                    Vec::new()
-                }
-            }
-        } else {
-            let mut entries = Vec::new();
-            for category in categories.iter() {
-                if entries.len() >= max_entries {
-                    break;
-                }
-                match miniflux.get_category_entries(*category as i32).await {
-                    Ok(entries_category) => {
-                        let remaining = max_entries - entries.len();
-                        entries.extend(entries_category.into_iter().take(remaining));
-                    }
-                    Err(e) => {
-                        error!("Error getting entries from category {}: {}", category, e);
-                    }
-                }
-            }
-            entries
-        };
-        let mut news = Vec::new();
-        for (index, entry) in entries.as_slice().iter().take(max_entries).enumerate() {
-            debug!("Entry {}: {}", index, entry);
-            let id = entry["id"].as_u64().unwrap_or(0);
-            let title = entry["title"].as_str().unwrap_or("No title");
-            let url = entry["url"].as_str().unwrap_or("No URL");
-            let resume = entry["content"].as_str().unwrap_or("No content");
-            let author = entry["author"].as_str().unwrap_or("No author");
-            let feed = entry["feed"].as_object().unwrap();
-            let feed_title = feed["title"].as_str().unwrap_or("No feed title");
-            let published_at = entry["published_at"].as_str().unwrap_or("No published_at");
-            news.push(json!({
-                "url": url,
-                "title": title,
-                "feed_title": feed_title,
-                "published_at": published_at,
-                "author": author,
-                "resume": resume,
-            }));
-            if let Err(response) = miniflux.mark_as_read(id).await {
-                error!("Error marking entry {} as read: {}", id, response);
-            }
-        }
-        if news.is_empty() {
-            info!("No new entries");
-        } else {
-            match model.process_news(&news).await {
-                Ok(message) => {
-                    debug!("Message: {:?}", message);
-                    match serde_json::from_str::<Value>(&message) {
-                        Ok(value) => {
-                            debug!("Value: {:?}", value);
-                            let news = value
-                                .get("news")
-                                .and_then(|v| v.as_array())
-                                .unwrap_or(&vec![])
-                                .iter()
-                                .map(|v| {
-                                    format!(
-                                        "<h3><a href=\"{}\">{}</a></h3><p>{}</p><br>",
-                                        v.get("url").unwrap().as_str().unwrap_or(""),
-                                        v.get("title").unwrap().as_str().unwrap_or(""),
-                                        v.get("summary").unwrap().as_str().unwrap_or("")
-                                    )
-                                })
-                                .collect::<Vec<_>>()
-                                .join("");
-                            match matrix.post(&news).await {
-                                Ok(response) => {
-                                    debug!("Matrix response: {:?}", response);
-                                }
-                                Err(e) => {
-                                    error!("Error sending news to Matrix: {}", e);
-                                }
-                            }
-                            let telegram_news = value
-                                .get("news")
-                                .and_then(|v| v.as_array())
-                                .unwrap_or(&vec![])
-                                .iter()
-                                .map(|v| {
-                                    format!(
-                                        "[{}]({})\n{}\n\n",
-                                        escape(v.get("title").unwrap().as_str().unwrap_or("")),
-                                        escape(v.get("url").unwrap().as_str().unwrap_or("")),
-                                        escape(v.get("summary").unwrap().as_str().unwrap_or(""))
-                                    )
-                                })
-                                .collect::<Vec<_>>()
-                                .join("");
-                            match telegram.send_message(&telegram_news).await {
-                                Ok(response) => {
-                                    debug!("Telegram response: {:?}", response);
-                                }
-                                Err(e) => {
-                                    error!("Error sending message to Telegram: {}", e);
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            error!("Error: {}", e);
-                        }
+    )
+    .with_backend(model_backend)
+    .with_json_schema(env::var("MODEL_JSON_SCHEMA").map(|value| value == "true").unwrap_or(false))
+    .with_max_tokens(
+        env::var("MODEL_MAX_TOKENS")
+            .ok()
+            .and_then(|value| value.parse::<u32>().ok())
+            .unwrap_or(4096),
+    )
+    .with_client(http)
+    .with_retry(retry);
+    let per_entry = env::var("SUMMARIZE_PER_ENTRY").map(|value| value == "true").unwrap_or(false);
+    let token_budget = env::var("MODEL_TOKEN_BUDGET")
+        .ok()
+        .and_then(|value| value.parse::<usize>().ok())
+        .filter(|budget| *budget > 0);
+    let summarizer: Box<dyn Summarizer> = match (per_entry, token_budget) {
+        // One model call per entry, reusing the cached summaries
+        (true, _) => {
+            let concurrency = env::var("SUMMARIZE_CONCURRENCY")
+                .ok()
+                .and_then(|value| value.parse::<usize>().ok())
+                .unwrap_or(4);
+            debug!("Summarizing per entry, concurrency: {}", concurrency);
+            let summarizer = PerEntrySummarizer::new(model, concurrency);
+            match env::var("SUMMARY_CACHE_DIR") {
+                Ok(dir) => {
+                    let mut cache = SummaryCache::new(dir);
+                    if let Some(days) = env::var("SUMMARY_CACHE_MAX_AGE_DAYS").ok().and_then(|value| value.parse::<u64>().ok()) {
+                        cache = cache.with_max_age(time::Duration::from_secs(days * 24 * 60 * 60));
                     }
+                    Box::new(summarizer.with_cache(cache))
                 }
-                Err(e) => {
-                    error!("Error: {}", e);
-                }
+                Err(_) => Box::new(summarizer),
             }
         }
-        info!("Sleeping for {:?} seconds", sleep_time);
-        tokio::time::sleep(sleep_time).await;
-    }
-}
-
-fn escape(text: &str) -> String {
-    let reserved = r#"_*[]()~`>#+-=|{}.!\\"#;
-    let mut escaped = String::new();
-    for c in text.chars() {
-        if reserved.contains(c) {
-            escaped.push('\\');
+        // Splits big digests in several model calls
+        (false, Some(budget)) => {
+            let reduce = env::var("MODEL_REDUCE").map(|value| value == "true").unwrap_or(false);
+            debug!("Token budget: {}, reduce: {}", budget, reduce);
+            Box::new(BatchSummarizer::new(model, budget).with_reduce(reduce))
         }
-        escaped.push(c);
-    }
-    escaped
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_escape_simple_text() {
-        let text = "hello world";
-        let result = escape(text);
-        assert_eq!(result, "hello world");
-    }
-
-    #[test]
-    fn test_escape_text_with_markdown_chars() {
-        let text = "hello*world";
-        let result = escape(text);
-        assert_eq!(result, "hello\\*world");
-    }
-
-    #[test]
-    fn test_escape_text_with_multiple_special_chars() {
-        let text = "_bold_ *italic* [link](url)";
-        let result = escape(text);
-        assert_eq!(result, "\\_bold\\_ \\*italic\\* \\[link\\]\\(url\\)");
+        (false, None) => Box::new(model),
+    };
+    let mut pipeline = Pipeline::new(MinifluxFetcher::new(miniflux.clone(), categories), summarizer, miniflux.clone())
+        .with_max_entries(max_entries)
+        .with_ack_policy(ack_policy)
+        .with_per_item(per_item)
+        .with_attach_media(attach_media);
+    if let Some(cache) = icon_cache {
+        pipeline = pipeline.with_icons(cache, miniflux.clone());
     }
-
-    #[test]
-    fn test_escape_empty_string() {
-        let text = "";
-        let result = escape(text);
-        assert_eq!(result, "");
+    if let Some(fetcher) = content_fetcher {
+        pipeline = pipeline.with_enricher(ContentEnricher::new(fetcher, miniflux));
     }
-
-    #[test]
-    fn test_escape_all_special_chars() {
-        let text = "_*[]()~`>#+-=|{}.!\\";
-        let result = escape(text);
-        assert_eq!(
-            result,
-            "\\_\\*\\[\\]\\(\\)\\~\\`\\>\\#\\+\\-\\=\\|\\{\\}\\.\\!\\\\"
-        );
+    pipeline = pipeline.with_enricher(normalizer);
+    if let Some(matrix) = matrix {
+        info!("Notifying to Matrix");
+        pipeline = pipeline.with_notifier(matrix);
     }
-
-    #[test]
-    fn test_escape_mixed_content() {
-        let text = "Price: $100 + tax";
-        let result = escape(text);
-        assert_eq!(result, "Price: $100 \\+ tax");
-    }
-
-    #[test]
-    fn test_escape_backslash() {
-        let text = "C:\\Users\\test";
-        let result = escape(text);
-        assert_eq!(result, "C:\\\\Users\\\\test");
+    if let Some(telegram) = telegram {
+        info!("Notifying to Telegram");
+        pipeline = pipeline.with_notifier(telegram);
     }
-
-    #[test]
-    fn test_escape_complex_markdown() {
-        let text = "**bold** _italic_ [link](url)";
-        let result = escape(text);
-        assert_eq!(result, "\\*\\*bold\\*\\* \\_italic\\_ \\[link\\]\\(url\\)");
+    loop {
+        match pipeline.run_once().await {
+            Ok(report) => debug!("Run report: {:?}", report),
+            Err(e) => {
+                error!("Error: {}", e);
+                if e.downcast_ref::<MinifluxError>().is_some_and(|e| e.is_unauthorized()) {
+                    error!("Miniflux rejected the credentials, check MINIFLUX_TOKEN or MINIFLUX_USERNAME/MINIFLUX_PASSWORD");
+                    std::process::exit(1);
+                }
+            }
+        }
+        info!("Sleeping for {:?} seconds", sleep_time);
+        tokio::time::sleep(sleep_time).await;
     }
 }
@@ -0,0 +1,45 @@
+use reqwest::RequestBuilder;
+use serde::{Deserialize, Serialize};
+
+// Serialized flat next to the url, so `{"url":..,"token":..}` keeps working
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Auth {
+    Token { token: String },
+    Basic { username: String, password: String },
+}
+
+impl Auth {
+    pub fn token(token: &str) -> Self {
+        Auth::Token {
+            token: token.to_string(),
+        }
+    }
+
+    pub fn basic(username: &str, password: &str) -> Self {
+        Auth::Basic {
+            username: username.to_string(),
+            password: password.to_string(),
+        }
+    }
+
+    pub(crate) fn apply(&self, request: RequestBuilder) -> RequestBuilder {
+        match self {
+            Auth::Token { token } => request.header("X-Auth-Token", token),
+            Auth::Basic { username, password } => request.basic_auth(username, Some(password)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Auth;
+
+    #[test]
+    fn test_auth_deserialize() {
+        let token: Auth = serde_json::from_str(r#"{"token":"abc"}"#).unwrap();
+        assert_eq!(token, Auth::token("abc"));
+        let basic: Auth = serde_json::from_str(r#"{"username":"admin","password":"secret"}"#).unwrap();
+        assert_eq!(basic, Auth::basic("admin", "secret"));
+    }
+}
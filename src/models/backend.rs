@@ -0,0 +1,259 @@
+use async_trait::async_trait;
+use reqwest::{Client, Response};
+use serde_json::{json, Value};
+use std::fmt;
+use tracing::{debug, warn};
+
+use super::{CustomError, Digest, RetryPolicy, RetryRequest};
+
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+// One call to the model server, `Model` builds the conversation and each
+// backend only speaks its API
+pub struct ChatRequest<'a> {
+    pub client: &'a Client,
+    pub retry: &'a RetryPolicy,
+    pub url: &'a str,
+    pub api_key: &'a str,
+    pub model: &'a str,
+    pub system: &'a str,
+    pub messages: &'a [Value],
+    // Backends honour the options their API supports
+    pub max_tokens: u32,
+    pub json_schema: bool,
+}
+
+impl ChatRequest<'_> {
+    fn endpoint(&self, path: &str) -> String {
+        format!("{}{}", self.url.trim_end_matches('/'), path)
+    }
+
+    // The system message first, for the APIs without a `system` field
+    fn with_system(&self) -> Vec<Value> {
+        let mut chat = vec![json!({
+            "role": "system",
+            "content": self.system
+        })];
+        chat.extend_from_slice(self.messages);
+        chat
+    }
+}
+
+// Parses the answer only once the status says it is one, the error pages of
+// proxies and gateways are rarely JSON
+async fn answer(response: Response, provider: &str) -> Result<Value, CustomError> {
+    let status = response.status();
+    if status.is_success() {
+        let answer = response.json::<Value>().await?;
+        debug!("Response: {:?}", answer);
+        return Ok(answer);
+    }
+    let body = response.text().await?;
+    // OpenAI and Anthropic nest the message in an object, Ollama does not
+    let message = serde_json::from_str::<Value>(&body).ok().and_then(|error| {
+        let error = error.get("error")?;
+        error
+            .get("message")
+            .unwrap_or(error)
+            .as_str()
+            .map(|message| message.to_string())
+    });
+    Err(format!("{} error {}: {}", provider, status, message.unwrap_or(body).trim()).into())
+}
+
+// A chat API, implement it to summarize with a provider this crate does not know
+#[async_trait]
+pub trait ChatBackend: Send + Sync {
+    fn name(&self) -> &str;
+
+    // Returns the text of the answer
+    async fn chat(&self, request: &ChatRequest<'_>) -> Result<String, CustomError>;
+}
+
+impl fmt::Debug for dyn ChatBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+// The backends built in, by the name used in `MODEL_PROVIDER`
+pub fn backend(provider: &str) -> Result<Box<dyn ChatBackend>, String> {
+    match provider.to_lowercase().as_str() {
+        "openai" => Ok(Box::new(OpenAiBackend)),
+        "anthropic" | "claude" => Ok(Box::new(AnthropicBackend)),
+        "ollama" => Ok(Box::new(OllamaBackend)),
+        _ => Err(format!("Unknown model provider: {}", provider)),
+    }
+}
+
+// Any OpenAI compatible `/v1/chat/completions` endpoint
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OpenAiBackend;
+
+#[async_trait]
+impl ChatBackend for OpenAiBackend {
+    fn name(&self) -> &str {
+        "openai"
+    }
+
+    async fn chat(&self, request: &ChatRequest<'_>) -> Result<String, CustomError> {
+        debug!("Processing news...");
+        let url = request.endpoint("/v1/chat/completions");
+        debug!("URL: {}", url);
+        let mut content = json!({
+            "model": request.model,
+            "messages": request.with_system()
+        });
+        if request.json_schema {
+            content["response_format"] = json!({
+                "type": "json_schema",
+                "json_schema": {
+                    "name": "digest",
+                    "strict": true,
+                    "schema": Digest::schema()
+                }
+            });
+        }
+        let response = request
+            .client
+            .post(&url)
+            .header("content-type", "application/json")
+            .header("Authorization", format!("Bearer {}", request.api_key))
+            .json(&content)
+            .send_with(request.retry)
+            .await?;
+        let response = answer(response, "OpenAI").await?;
+        let content = response
+            .get("choices")
+            .ok_or("No content")?
+            .as_array()
+            .ok_or("No array")?
+            .first()
+            .ok_or("No first element")?
+            .get("message")
+            .ok_or("No message")?
+            .get("content")
+            .ok_or("No content")?
+            .as_str()
+            .ok_or("No string")?;
+        debug!("Response: {:?}", content);
+        Ok(content.to_string())
+    }
+}
+
+// Anthropic Messages API, `/v1/messages`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AnthropicBackend;
+
+#[async_trait]
+impl ChatBackend for AnthropicBackend {
+    fn name(&self) -> &str {
+        "anthropic"
+    }
+
+    async fn chat(&self, request: &ChatRequest<'_>) -> Result<String, CustomError> {
+        debug!("Processing news...");
+        let url = request.endpoint("/v1/messages");
+        debug!("URL: {}", url);
+        let content = json!({
+            "model": request.model,
+            "max_tokens": request.max_tokens,
+            "system": request.system,
+            "messages": request.messages
+        });
+        let response = request
+            .client
+            .post(&url)
+            .header("content-type", "application/json")
+            .header("x-api-key", request.api_key)
+            .header("anthropic-version", ANTHROPIC_VERSION)
+            .json(&content)
+            .send_with(request.retry)
+            .await?;
+        let response = answer(response, "Anthropic").await?;
+        match response.get("stop_reason").and_then(|r| r.as_str()) {
+            // A cut answer is not valid JSON, better to fail than to send half a digest
+            Some("max_tokens") => {
+                return Err(format!(
+                    "Anthropic answer truncated at {} tokens, raise MODEL_MAX_TOKENS",
+                    request.max_tokens
+                )
+                .into())
+            }
+            Some("refusal") => return Err("Anthropic refused to summarize the news".into()),
+            Some(reason) => debug!("Stop reason: {}", reason),
+            None => warn!("Anthropic answer without stop reason"),
+        }
+        let content = response
+            .get("content")
+            .ok_or("No content")?
+            .as_array()
+            .ok_or("No array")?
+            .iter()
+            .filter(|block| block.get("type").and_then(|t| t.as_str()) == Some("text"))
+            .filter_map(|block| block.get("text").and_then(|t| t.as_str()))
+            .collect::<String>();
+        if content.is_empty() {
+            return Err("No text in the Anthropic answer".into());
+        }
+        debug!("Response: {:?}", content);
+        Ok(content)
+    }
+}
+
+// Ollama native `/api/chat`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OllamaBackend;
+
+#[async_trait]
+impl ChatBackend for OllamaBackend {
+    fn name(&self) -> &str {
+        "ollama"
+    }
+
+    async fn chat(&self, request: &ChatRequest<'_>) -> Result<String, CustomError> {
+        debug!("Processing news...");
+        let url = request.endpoint("/api/chat");
+        debug!("URL: {}", url);
+        let content = json!({
+            "model": request.model,
+            "stream": false,
+            "format": Digest::schema(),
+            "messages": request.with_system()
+        });
+        let mut builder = request
+            .client
+            .post(&url)
+            .header("content-type", "application/json")
+            .json(&content);
+        // A local Ollama needs no key, a proxied one may
+        if !request.api_key.is_empty() {
+            builder = builder.header("Authorization", format!("Bearer {}", request.api_key));
+        }
+        let response = builder.send_with(request.retry).await?;
+        let response = answer(response, "Ollama").await?;
+        let content = response
+            .get("message")
+            .ok_or("No message")?
+            .get("content")
+            .ok_or("No content")?
+            .as_str()
+            .ok_or("No string")?;
+        debug!("Response: {:?}", content);
+        Ok(content.to_string())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::backend;
+
+    #[test]
+    fn test_backend() {
+        assert_eq!(backend("OpenAI").unwrap().name(), "openai");
+        assert_eq!(backend("anthropic").unwrap().name(), "anthropic");
+        assert_eq!(backend("claude").unwrap().name(), "anthropic");
+        assert_eq!(backend("ollama").unwrap().name(), "ollama");
+        assert!(backend("bard").is_err());
+    }
+}
@@ -0,0 +1,26 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Category {
+    pub id: i64,
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub user_id: i64,
+    #[serde(default)]
+    pub hide_globally: bool,
+}
+
+#[cfg(test)]
+mod test {
+    use super::Category;
+
+    #[test]
+    fn test_category_deserialize() {
+        let json = r#"{"id":1,"title":"Tech","user_id":3}"#;
+        let category: Category = serde_json::from_str(json).unwrap();
+        assert_eq!(category.id, 1);
+        assert_eq!(category.title, "Tech");
+        assert!(!category.hide_globally);
+    }
+}
@@ -0,0 +1,121 @@
+use futures::{stream, StreamExt};
+use tracing::{debug, warn};
+
+use super::{Entry, MinifluxClient};
+
+const MIN_LENGTH: usize = 500;
+const CONCURRENCY: usize = 4;
+
+// Replaces short entry contents (usually feed teasers) with the article
+// scraped by Miniflux, keeping the original when scraping fails
+#[derive(Debug, Clone)]
+pub struct ContentFetcher {
+    pub min_length: usize,
+    pub concurrency: usize,
+}
+
+impl Default for ContentFetcher {
+    fn default() -> Self {
+        ContentFetcher {
+            min_length: MIN_LENGTH,
+            concurrency: CONCURRENCY,
+        }
+    }
+}
+
+impl ContentFetcher {
+    pub fn new(min_length: usize, concurrency: usize) -> Self {
+        ContentFetcher {
+            min_length,
+            concurrency: concurrency.max(1),
+        }
+    }
+
+    pub fn needs_fetch(&self, entry: &Entry) -> bool {
+        entry.content.chars().count() < self.min_length
+    }
+
+    pub async fn enrich(&self, miniflux: &MinifluxClient, entries: Vec<Entry>) -> Vec<Entry> {
+        stream::iter(entries)
+            .map(|mut entry| async move {
+                if !self.needs_fetch(&entry) {
+                    return entry;
+                }
+                match miniflux.get_content(entry.id).await {
+                    Ok(content) if content.chars().count() > entry.content.chars().count() => {
+                        debug!("Fetched full content for entry {}", entry.id);
+                        entry.content = content;
+                    }
+                    Ok(_) => debug!("Fetched content for entry {} is not longer, keeping original", entry.id),
+                    Err(e) => warn!("Error fetching content for entry {}: {}", entry.id, e),
+                }
+                entry
+            })
+            .buffered(self.concurrency)
+            .collect()
+            .await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ContentFetcher;
+    use crate::models::{Entry, MinifluxClient, RetryPolicy};
+
+    fn entry(id: i64, content: &str) -> Entry {
+        Entry {
+            id,
+            content: content.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_needs_fetch() {
+        let fetcher = ContentFetcher::new(10, 2);
+        assert!(fetcher.needs_fetch(&entry(1, "short")));
+        assert!(!fetcher.needs_fetch(&entry(1, "long enough content")));
+    }
+
+    #[test]
+    fn test_concurrency_is_at_least_one() {
+        assert_eq!(ContentFetcher::new(10, 0).concurrency, 1);
+    }
+
+    #[tokio::test]
+    async fn test_enrich_with_mock() {
+        let mut server = mockito::Server::new_async().await;
+        let fetched = server.mock("GET", "/v1/entries/1/fetch-content")
+            .with_status(200)
+            .with_body(r#"{"content":"<p>The whole article, much longer</p>"}"#)
+            .create_async()
+            .await;
+        let failed = server.mock("GET", "/v1/entries/2/fetch-content")
+            .with_status(500)
+            .with_body(r#"{"error_message":"scraper failed"}"#)
+            .create_async()
+            .await;
+        let skipped = server.mock("GET", "/v1/entries/3/fetch-content")
+            .expect(0)
+            .create_async()
+            .await;
+
+        let miniflux = MinifluxClient::with_base_url(
+            server.host_with_port(),
+            "test_token".to_string(),
+            "http".to_string(),
+        )
+        .with_retry(RetryPolicy::none());
+        let fetcher = ContentFetcher::new(15, 2);
+        let entries = fetcher
+            .enrich(&miniflux, vec![entry(1, "teaser"), entry(2, "teaser"), entry(3, "already complete")])
+            .await;
+
+        fetched.assert_async().await;
+        failed.assert_async().await;
+        skipped.assert_async().await;
+        assert_eq!(entries[0].content, "<p>The whole article, much longer</p>");
+        assert_eq!(entries[1].content, "teaser");
+        assert_eq!(entries[2].content, "already complete");
+    }
+}
@@ -0,0 +1,135 @@
+use async_trait::async_trait;
+use std::{collections::BTreeSet, str::FromStr};
+use tracing::{debug, info};
+
+use super::CustomError;
+
+// Marks delivered entries as read, the last stage of the pipeline
+#[async_trait]
+pub trait Acknowledger: Send + Sync {
+    async fn acknowledge(&self, entry_ids: Vec<i64>) -> Result<(), CustomError>;
+}
+
+// How many notifiers must confirm a digest before its entries are marked read
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AckPolicy {
+    #[default]
+    Any,
+    All,
+}
+
+impl FromStr for AckPolicy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "any" => Ok(AckPolicy::Any),
+            "all" => Ok(AckPolicy::All),
+            _ => Err(format!("Unknown acknowledge policy: {}", value)),
+        }
+    }
+}
+
+impl AckPolicy {
+    pub fn is_confirmed(&self, delivered: usize, attempted: usize) -> bool {
+        match self {
+            // Nothing was sent, so nothing can be marked as read
+            _ if attempted == 0 => false,
+            AckPolicy::Any => delivered > 0,
+            AckPolicy::All => delivered == attempted,
+        }
+    }
+}
+
+// Entries already delivered whose read status could not be stored yet
+#[derive(Debug, Clone, Default)]
+pub struct DeliveryTracker {
+    pub policy: AckPolicy,
+    pending: BTreeSet<i64>,
+}
+
+impl DeliveryTracker {
+    pub fn new(policy: AckPolicy) -> Self {
+        DeliveryTracker {
+            policy,
+            pending: BTreeSet::new(),
+        }
+    }
+
+    pub fn is_pending(&self, entry_id: i64) -> bool {
+        self.pending.contains(&entry_id)
+    }
+
+    pub fn pending(&self) -> Vec<i64> {
+        self.pending.iter().copied().collect()
+    }
+
+    pub fn queue(&mut self, entry_ids: impl IntoIterator<Item = i64>) {
+        self.pending.extend(entry_ids);
+    }
+
+    // Marks every pending entry as read in a single call, keeping them on failure
+    pub async fn flush<A: Acknowledger + ?Sized>(&mut self, acknowledger: &A) -> Result<(), CustomError> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let entry_ids = self.pending();
+        debug!("Acknowledging entries: {:?}", entry_ids);
+        acknowledger.acknowledge(entry_ids).await?;
+        info!("{} entries marked as read", self.pending.len());
+        self.pending.clear();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AckPolicy, DeliveryTracker};
+    use crate::models::{MinifluxClient, RetryPolicy};
+
+    #[test]
+    fn test_ack_policy() {
+        assert!(AckPolicy::Any.is_confirmed(1, 2));
+        assert!(!AckPolicy::Any.is_confirmed(0, 2));
+        assert!(AckPolicy::All.is_confirmed(2, 2));
+        assert!(!AckPolicy::All.is_confirmed(1, 2));
+        assert!(!AckPolicy::All.is_confirmed(0, 0));
+        assert!(!AckPolicy::Any.is_confirmed(0, 0));
+        assert_eq!("ALL".parse::<AckPolicy>(), Ok(AckPolicy::All));
+        assert!("some".parse::<AckPolicy>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_flush_keeps_pending_on_failure() {
+        let mut server = mockito::Server::new_async().await;
+        let failing = server.mock("PUT", "/v1/entries")
+            .with_status(500)
+            .expect(1)
+            .create_async()
+            .await;
+        let working = server.mock("PUT", "/v1/entries")
+            .match_body(mockito::Matcher::Json(serde_json::json!({"entry_ids": [1, 2, 3], "status": "read"})))
+            .with_status(204)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let miniflux = MinifluxClient::with_base_url(
+            server.host_with_port(),
+            "test_token".to_string(),
+            "http".to_string(),
+        )
+        .with_retry(RetryPolicy::none());
+        let mut tracker = DeliveryTracker::new(AckPolicy::Any);
+        tracker.queue([2, 1]);
+
+        assert!(tracker.flush(&miniflux).await.is_err());
+        assert!(tracker.is_pending(1));
+        tracker.queue([3]);
+        assert!(tracker.flush(&miniflux).await.is_ok());
+        assert!(tracker.pending().is_empty());
+        assert!(tracker.flush(&miniflux).await.is_ok());
+        failing.assert_async().await;
+        working.assert_async().await;
+    }
+}
@@ -0,0 +1,117 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+// One news item as returned by the model
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DigestItem {
+    pub title: String,
+    pub url: String,
+    pub summary: String,
+}
+
+// The model answer, a `news` array of summarized items
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Digest {
+    pub news: Vec<DigestItem>,
+    // Entries the summarizer could not handle, they must stay unread
+    #[serde(skip)]
+    pub failed: Vec<i64>,
+}
+
+impl Digest {
+    // JSON schema sent to the providers that support structured output
+    pub fn schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "news": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "title": {"type": "string"},
+                            "url": {"type": "string"},
+                            "summary": {"type": "string"}
+                        },
+                        "required": ["title", "url", "summary"],
+                        "additionalProperties": false
+                    }
+                }
+            },
+            "required": ["news"],
+            "additionalProperties": false
+        })
+    }
+
+    // Parses and validates a model answer, tolerating markdown code fences
+    pub fn parse(answer: &str) -> Result<Digest, String> {
+        let digest = serde_json::from_str::<Digest>(strip_fences(answer))
+            .map_err(|e| format!("The answer is not a valid digest: {}", e))?;
+        digest.validate()?;
+        Ok(digest)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        for (position, item) in self.news.iter().enumerate() {
+            if item.title.trim().is_empty() {
+                return Err(format!("Item {} has an empty title", position));
+            }
+            if !item.url.starts_with("http://") && !item.url.starts_with("https://") {
+                return Err(format!("Item {} has an invalid url: {:?}", position, item.url));
+            }
+            if item.summary.trim().is_empty() {
+                return Err(format!("Item {} has an empty summary", position));
+            }
+        }
+        Ok(())
+    }
+}
+
+// Removes the ```json fences some models wrap their answer in
+pub fn strip_fences(answer: &str) -> &str {
+    let answer = answer.trim();
+    let Some(fenced) = answer.strip_prefix("```") else {
+        return answer;
+    };
+    // Skip the language tag, if any
+    let body = fenced.split_once('\n').map(|(_, body)| body).unwrap_or("");
+    body.trim_end().strip_suffix("```").unwrap_or(body).trim()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{strip_fences, Digest, DigestItem};
+
+    #[test]
+    fn test_strip_fences() {
+        assert_eq!(strip_fences("  {\"news\":[]} "), "{\"news\":[]}");
+        assert_eq!(strip_fences("```json\n{\"news\":[]}\n```"), "{\"news\":[]}");
+        assert_eq!(strip_fences("```\n{\"news\":[]}\n```\n"), "{\"news\":[]}");
+        assert_eq!(strip_fences("```json\n{\"news\":[]}"), "{\"news\":[]}");
+    }
+
+    #[test]
+    fn test_parse() {
+        let digest = Digest::parse(
+            "```json\n{\"news\":[{\"title\":\"One\",\"url\":\"https://example.org/1\",\"summary\":\"First\"}]}\n```",
+        )
+        .unwrap();
+        assert_eq!(
+            digest.news,
+            vec![DigestItem {
+                title: "One".to_string(),
+                url: "https://example.org/1".to_string(),
+                summary: "First".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(Digest::parse("Here are the news").is_err());
+        let missing = Digest::parse(r#"{"news":[{"title":"One","url":"https://example.org/1"}]}"#);
+        assert!(missing.unwrap_err().contains("summary"));
+        let invalid = Digest::parse(r#"{"news":[{"title":"One","url":"example","summary":"First"}]}"#);
+        assert!(invalid.unwrap_err().contains("invalid url"));
+    }
+}
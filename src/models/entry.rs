@@ -0,0 +1,203 @@
+use serde::{Deserialize, Serialize};
+
+use super::Feed;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryStatus {
+    #[default]
+    Unread,
+    Read,
+    Removed,
+}
+
+impl EntryStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EntryStatus::Unread => "unread",
+            EntryStatus::Read => "read",
+            EntryStatus::Removed => "removed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Enclosure {
+    pub id: i64,
+    #[serde(default)]
+    pub user_id: i64,
+    #[serde(default)]
+    pub entry_id: i64,
+    #[serde(default)]
+    pub url: String,
+    #[serde(default)]
+    pub mime_type: String,
+    #[serde(default)]
+    pub size: i64,
+    #[serde(default)]
+    pub media_progression: i64,
+}
+
+impl Enclosure {
+    pub fn is_image(&self) -> bool {
+        self.mime_type.starts_with("image/")
+    }
+
+    pub fn is_audio(&self) -> bool {
+        self.mime_type.starts_with("audio/")
+    }
+
+    pub fn is_video(&self) -> bool {
+        self.mime_type.starts_with("video/")
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Entry {
+    pub id: i64,
+    #[serde(default)]
+    pub user_id: i64,
+    #[serde(default)]
+    pub feed_id: i64,
+    #[serde(default)]
+    pub status: EntryStatus,
+    #[serde(default)]
+    pub hash: String,
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub url: String,
+    #[serde(default)]
+    pub comments_url: String,
+    #[serde(default)]
+    pub published_at: String,
+    #[serde(default)]
+    pub created_at: String,
+    #[serde(default)]
+    pub changed_at: String,
+    #[serde(default)]
+    pub content: String,
+    #[serde(default)]
+    pub author: String,
+    #[serde(default)]
+    pub share_code: String,
+    #[serde(default)]
+    pub starred: bool,
+    #[serde(default)]
+    pub reading_time: i64,
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub enclosures: Vec<Enclosure>,
+    #[serde(default)]
+    pub feed: Option<Feed>,
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub tags: Vec<String>,
+}
+
+impl Entry {
+    pub fn lead_image(&self) -> Option<&Enclosure> {
+        self.enclosures
+            .iter()
+            .find(|enclosure| enclosure.is_image() && !enclosure.url.is_empty())
+    }
+
+    // Podcast episode or video attached to the entry
+    pub fn media(&self) -> Option<&Enclosure> {
+        self.enclosures
+            .iter()
+            .find(|enclosure| (enclosure.is_audio() || enclosure.is_video()) && !enclosure.url.is_empty())
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EntriesResponse {
+    #[serde(default)]
+    pub total: i64,
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub entries: Vec<Entry>,
+}
+
+// Miniflux sends `null` instead of `[]` for some empty collections
+pub(crate) fn null_as_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Default + Deserialize<'de>,
+{
+    Ok(Option::<T>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{EntriesResponse, Entry, EntryStatus};
+
+    #[test]
+    fn test_entry_deserialize_minimal() {
+        let json = r#"{"id":42,"title":"Hello"}"#;
+        let entry: Entry = serde_json::from_str(json).unwrap();
+        assert_eq!(entry.id, 42);
+        assert_eq!(entry.title, "Hello");
+        assert_eq!(entry.status, EntryStatus::Unread);
+        assert!(entry.feed.is_none());
+        assert!(entry.enclosures.is_empty());
+    }
+
+    #[test]
+    fn test_entry_deserialize_nulls() {
+        let json = r#"{"id":1,"status":"read","enclosures":null,"tags":null,"feed":null}"#;
+        let entry: Entry = serde_json::from_str(json).unwrap();
+        assert_eq!(entry.status, EntryStatus::Read);
+        assert!(entry.enclosures.is_empty());
+        assert!(entry.tags.is_empty());
+        assert!(entry.feed.is_none());
+    }
+
+    #[test]
+    fn test_entry_deserialize_full() {
+        let json = r#"{
+            "id":888,
+            "user_id":1,
+            "feed_id":42,
+            "status":"unread",
+            "hash":"abc",
+            "title":"Entry Title",
+            "url":"http://example.org/article.html",
+            "published_at":"2016-12-12T16:15:19Z",
+            "content":"<p>HTML</p>",
+            "author":"Someone",
+            "starred":true,
+            "reading_time":3,
+            "enclosures":[{"id":7,"url":"http://example.org/a.mp3","mime_type":"audio/mpeg","size":1024}],
+            "feed":{"id":42,"title":"New Feed Title","category":{"id":22,"title":"Tech"}}
+        }"#;
+        let entry: Entry = serde_json::from_str(json).unwrap();
+        assert!(entry.starred);
+        assert_eq!(entry.enclosures[0].mime_type, "audio/mpeg");
+        let feed = entry.feed.unwrap();
+        assert_eq!(feed.title, "New Feed Title");
+        assert_eq!(feed.category.unwrap().title, "Tech");
+    }
+
+    #[test]
+    fn test_entry_lead_image_and_media() {
+        let json = r#"{
+            "id":1,
+            "enclosures":[
+                {"id":1,"url":"","mime_type":"image/png"},
+                {"id":2,"url":"http://example.org/episode.mp3","mime_type":"audio/mpeg"},
+                {"id":3,"url":"http://example.org/cover.jpg","mime_type":"image/jpeg"}
+            ]
+        }"#;
+        let entry: Entry = serde_json::from_str(json).unwrap();
+        assert_eq!(entry.lead_image().unwrap().id, 3);
+        assert_eq!(entry.media().unwrap().id, 2);
+        assert!(Entry::default().lead_image().is_none());
+        assert!(Entry::default().media().is_none());
+    }
+
+    #[test]
+    fn test_entries_response_deserialize() {
+        let json = r#"{"total":2,"entries":[{"id":1},{"id":2}]}"#;
+        let response: EntriesResponse = serde_json::from_str(json).unwrap();
+        assert_eq!(response.total, 2);
+        assert_eq!(response.entries.len(), 2);
+    }
+}
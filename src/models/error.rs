@@ -0,0 +1,147 @@
+use reqwest::StatusCode;
+use serde::Deserialize;
+use std::fmt;
+
+use super::retry;
+
+#[derive(Debug)]
+pub enum MinifluxError {
+    /// The request never got a response (DNS, TLS, timeout, connection reset...)
+    Transport(reqwest::Error),
+    /// The server answered successfully but the body was not what we expected
+    Decode(serde_json::Error),
+    /// The server answered with a non-success status
+    Api { status: StatusCode, message: String },
+    /// Feed discovery found nothing to subscribe to at the given URL
+    NoFeedFound(String),
+    /// The healthcheck answered, but not with "OK"
+    Unhealthy(String),
+}
+
+#[derive(Deserialize)]
+struct ErrorBody {
+    error_message: String,
+}
+
+impl MinifluxError {
+    pub fn from_response(status: StatusCode, body: &str) -> Self {
+        let message = match serde_json::from_str::<ErrorBody>(body) {
+            Ok(error) => error.error_message,
+            Err(_) => body.trim().to_string(),
+        };
+        MinifluxError::Api { status, message }
+    }
+
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            MinifluxError::Api { status, .. } => Some(*status),
+            MinifluxError::Transport(e) => e.status(),
+            MinifluxError::Decode(_) | MinifluxError::NoFeedFound(_) | MinifluxError::Unhealthy(_) => None,
+        }
+    }
+
+    pub fn is_unauthorized(&self) -> bool {
+        matches!(
+            self.status(),
+            Some(StatusCode::UNAUTHORIZED) | Some(StatusCode::FORBIDDEN)
+        )
+    }
+
+    pub fn is_not_found(&self) -> bool {
+        self.status() == Some(StatusCode::NOT_FOUND)
+    }
+
+    pub fn is_server_error(&self) -> bool {
+        self.status().is_some_and(|status| status.is_server_error())
+    }
+
+    /// Errors worth trying again later: network problems and the answers the
+    /// retry policy retries
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            MinifluxError::Transport(_) | MinifluxError::Unhealthy(_) => true,
+            MinifluxError::Api { status, .. } => retry::is_retryable(*status),
+            MinifluxError::Decode(_) | MinifluxError::NoFeedFound(_) => false,
+        }
+    }
+}
+
+impl fmt::Display for MinifluxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MinifluxError::Transport(e) => write!(f, "Miniflux transport error: {}", e),
+            MinifluxError::Decode(e) => write!(f, "Miniflux decoding error: {}", e),
+            MinifluxError::Api { status, message } => {
+                write!(f, "Miniflux API error ({}): {}", status, message)
+            }
+            MinifluxError::NoFeedFound(url) => write!(f, "No feed found at {}", url),
+            MinifluxError::Unhealthy(body) => write!(f, "Miniflux is unhealthy: {}", body),
+        }
+    }
+}
+
+impl std::error::Error for MinifluxError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MinifluxError::Transport(e) => Some(e),
+            MinifluxError::Decode(e) => Some(e),
+            MinifluxError::Api { .. } | MinifluxError::NoFeedFound(_) | MinifluxError::Unhealthy(_) => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for MinifluxError {
+    fn from(error: reqwest::Error) -> Self {
+        MinifluxError::Transport(error)
+    }
+}
+
+impl From<serde_json::Error> for MinifluxError {
+    fn from(error: serde_json::Error) -> Self {
+        MinifluxError::Decode(error)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::MinifluxError;
+    use reqwest::StatusCode;
+
+    #[test]
+    fn test_from_response_parses_error_message() {
+        let error = MinifluxError::from_response(
+            StatusCode::UNAUTHORIZED,
+            r#"{"error_message":"access unauthorized"}"#,
+        );
+        assert!(error.is_unauthorized());
+        assert!(!error.is_retryable());
+        assert_eq!(
+            error.to_string(),
+            "Miniflux API error (401 Unauthorized): access unauthorized"
+        );
+    }
+
+    #[test]
+    fn test_from_response_with_plain_body() {
+        let error = MinifluxError::from_response(StatusCode::BAD_GATEWAY, "Bad Gateway\n");
+        assert!(error.is_server_error());
+        assert!(error.is_retryable());
+        match error {
+            MinifluxError::Api { message, .. } => assert_eq!(message, "Bad Gateway"),
+            _ => panic!("expected an API error"),
+        }
+    }
+
+    #[test]
+    fn test_is_retryable_matches_retry_policy() {
+        assert!(MinifluxError::from_response(StatusCode::REQUEST_TIMEOUT, "").is_retryable());
+        assert!(!MinifluxError::from_response(StatusCode::NOT_IMPLEMENTED, "").is_retryable());
+    }
+
+    #[test]
+    fn test_decode_error_is_not_retryable() {
+        let error: MinifluxError = serde_json::from_str::<Vec<i64>>("{").unwrap_err().into();
+        assert!(error.status().is_none());
+        assert!(!error.is_retryable());
+    }
+}
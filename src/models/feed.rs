@@ -0,0 +1,418 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fmt};
+
+use super::Category;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FeedIcon {
+    #[serde(default)]
+    pub feed_id: i64,
+    #[serde(default)]
+    pub icon_id: i64,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Feed {
+    pub id: i64,
+    #[serde(default)]
+    pub user_id: i64,
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub site_url: String,
+    #[serde(default)]
+    pub feed_url: String,
+    #[serde(default)]
+    pub checked_at: String,
+    #[serde(default)]
+    pub etag_header: String,
+    #[serde(default)]
+    pub last_modified_header: String,
+    #[serde(default)]
+    pub parsing_error_message: String,
+    #[serde(default)]
+    pub parsing_error_count: i64,
+    #[serde(default)]
+    pub scraper_rules: String,
+    #[serde(default)]
+    pub rewrite_rules: String,
+    #[serde(default)]
+    pub blocklist_rules: String,
+    #[serde(default)]
+    pub keeplist_rules: String,
+    #[serde(default)]
+    pub crawler: bool,
+    #[serde(default)]
+    pub user_agent: String,
+    #[serde(default)]
+    pub cookie: String,
+    #[serde(default)]
+    pub username: String,
+    #[serde(default)]
+    pub password: String,
+    #[serde(default)]
+    pub disabled: bool,
+    #[serde(default)]
+    pub ignore_http_cache: bool,
+    #[serde(default)]
+    pub fetch_via_proxy: bool,
+    #[serde(default)]
+    pub hide_globally: bool,
+    #[serde(default)]
+    pub category: Option<Category>,
+    #[serde(default)]
+    pub icon: Option<FeedIcon>,
+}
+
+// Feeds end up in the debug logs with every entry, the credentials must not
+impl fmt::Debug for Feed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Feed")
+            .field("id", &self.id)
+            .field("user_id", &self.user_id)
+            .field("title", &self.title)
+            .field("site_url", &self.site_url)
+            .field("feed_url", &self.feed_url)
+            .field("checked_at", &self.checked_at)
+            .field("etag_header", &self.etag_header)
+            .field("last_modified_header", &self.last_modified_header)
+            .field("parsing_error_message", &self.parsing_error_message)
+            .field("parsing_error_count", &self.parsing_error_count)
+            .field("scraper_rules", &self.scraper_rules)
+            .field("rewrite_rules", &self.rewrite_rules)
+            .field("blocklist_rules", &self.blocklist_rules)
+            .field("keeplist_rules", &self.keeplist_rules)
+            .field("crawler", &self.crawler)
+            .field("user_agent", &self.user_agent)
+            .field("cookie", &redact(&self.cookie))
+            .field("username", &redact(&self.username))
+            .field("password", &redact(&self.password))
+            .field("disabled", &self.disabled)
+            .field("ignore_http_cache", &self.ignore_http_cache)
+            .field("fetch_via_proxy", &self.fetch_via_proxy)
+            .field("hide_globally", &self.hide_globally)
+            .field("category", &self.category)
+            .field("icon", &self.icon)
+            .finish()
+    }
+}
+
+// Shows whether a credential is set without showing it
+fn redact(secret: &str) -> &'static str {
+    if secret.is_empty() {
+        ""
+    } else {
+        "***"
+    }
+}
+
+fn redact_option(secret: &Option<String>) -> Option<&'static str> {
+    secret.as_deref().map(redact)
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Icon {
+    pub id: i64,
+    #[serde(default)]
+    pub data: String,
+    #[serde(default)]
+    pub mime_type: String,
+}
+
+impl Icon {
+    // `data` comes as `image/png;base64,<payload>`
+    pub fn bytes(&self) -> Result<Vec<u8>, base64::DecodeError> {
+        let payload = match self.data.split_once("base64,") {
+            Some((_, payload)) => payload,
+            None => self.data.as_str(),
+        };
+        STANDARD.decode(payload.trim())
+    }
+}
+
+// Read and unread entries per feed id
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FeedCounters {
+    #[serde(default)]
+    pub reads: HashMap<i64, i64>,
+    #[serde(default)]
+    pub unreads: HashMap<i64, i64>,
+}
+
+impl FeedCounters {
+    pub fn unread(&self, feed_id: i64) -> i64 {
+        self.unreads.get(&feed_id).copied().unwrap_or_default()
+    }
+
+    pub fn total_unread(&self) -> i64 {
+        self.unreads.values().sum()
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DiscoveredFeed {
+    pub url: String,
+    #[serde(default)]
+    pub title: String,
+    #[serde(default, rename = "type")]
+    pub kind: String,
+}
+
+impl DiscoveredFeed {
+    // Lower is better, Miniflux handles every format but Atom carries the
+    // richest metadata
+    fn rank(&self) -> usize {
+        match self.kind.as_str() {
+            "atom" => 0,
+            "rss" => 1,
+            "json" => 2,
+            _ => 3,
+        }
+    }
+
+    pub fn best(candidates: &[DiscoveredFeed], url: &str) -> Option<DiscoveredFeed> {
+        candidates
+            .iter()
+            .find(|candidate| candidate.url == url)
+            .or_else(|| candidates.iter().min_by_key(|candidate| candidate.rank()))
+            .cloned()
+    }
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct FeedCreationRequest {
+    pub feed_url: String,
+    pub category_id: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_agent: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cookie: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub crawler: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disabled: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ignore_http_cache: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fetch_via_proxy: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scraper_rules: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rewrite_rules: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blocklist_rules: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keeplist_rules: Option<String>,
+}
+
+impl fmt::Debug for FeedCreationRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FeedCreationRequest")
+            .field("feed_url", &self.feed_url)
+            .field("category_id", &self.category_id)
+            .field("user_agent", &self.user_agent)
+            .field("cookie", &redact_option(&self.cookie))
+            .field("username", &redact_option(&self.username))
+            .field("password", &redact_option(&self.password))
+            .field("crawler", &self.crawler)
+            .field("disabled", &self.disabled)
+            .field("ignore_http_cache", &self.ignore_http_cache)
+            .field("fetch_via_proxy", &self.fetch_via_proxy)
+            .field("scraper_rules", &self.scraper_rules)
+            .field("rewrite_rules", &self.rewrite_rules)
+            .field("blocklist_rules", &self.blocklist_rules)
+            .field("keeplist_rules", &self.keeplist_rules)
+            .finish()
+    }
+}
+
+impl FeedCreationRequest {
+    pub fn new(feed_url: &str, category_id: i64) -> Self {
+        FeedCreationRequest {
+            feed_url: feed_url.to_string(),
+            category_id,
+            ..Default::default()
+        }
+    }
+}
+
+// Only the fields that are set are sent, Miniflux keeps the rest untouched
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct FeedModificationRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub feed_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub site_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category_id: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_agent: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cookie: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub crawler: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disabled: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ignore_http_cache: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fetch_via_proxy: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hide_globally: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scraper_rules: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rewrite_rules: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blocklist_rules: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keeplist_rules: Option<String>,
+}
+
+impl fmt::Debug for FeedModificationRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FeedModificationRequest")
+            .field("feed_url", &self.feed_url)
+            .field("site_url", &self.site_url)
+            .field("title", &self.title)
+            .field("category_id", &self.category_id)
+            .field("user_agent", &self.user_agent)
+            .field("cookie", &redact_option(&self.cookie))
+            .field("username", &redact_option(&self.username))
+            .field("password", &redact_option(&self.password))
+            .field("crawler", &self.crawler)
+            .field("disabled", &self.disabled)
+            .field("ignore_http_cache", &self.ignore_http_cache)
+            .field("fetch_via_proxy", &self.fetch_via_proxy)
+            .field("hide_globally", &self.hide_globally)
+            .field("scraper_rules", &self.scraper_rules)
+            .field("rewrite_rules", &self.rewrite_rules)
+            .field("blocklist_rules", &self.blocklist_rules)
+            .field("keeplist_rules", &self.keeplist_rules)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{DiscoveredFeed, Feed, FeedCounters, FeedCreationRequest, FeedModificationRequest, Icon};
+
+    #[test]
+    fn test_feed_deserialize_minimal() {
+        let json = r#"{"id":42,"title":"Feed"}"#;
+        let feed: Feed = serde_json::from_str(json).unwrap();
+        assert_eq!(feed.id, 42);
+        assert_eq!(feed.title, "Feed");
+        assert!(feed.category.is_none());
+        assert!(feed.icon.is_none());
+    }
+
+    #[test]
+    fn test_feed_deserialize_with_icon() {
+        let json = r#"{"id":42,"feed_url":"https://example.org/feed.xml","icon":{"feed_id":42,"icon_id":11}}"#;
+        let feed: Feed = serde_json::from_str(json).unwrap();
+        assert_eq!(feed.feed_url, "https://example.org/feed.xml");
+        assert_eq!(feed.icon.unwrap().icon_id, 11);
+    }
+
+    #[test]
+    fn test_icon_deserialize() {
+        let json = r#"{"id":262,"data":"image/png;base64,iVBORw0KGgo=","mime_type":"image/png"}"#;
+        let icon: Icon = serde_json::from_str(json).unwrap();
+        assert_eq!(icon.id, 262);
+        assert_eq!(icon.mime_type, "image/png");
+        assert_eq!(icon.bytes().unwrap(), b"\x89PNG\r\n\x1a\n");
+    }
+
+    #[test]
+    fn test_feed_counters_deserialize() {
+        let json = r#"{"reads":{"1":5,"3":1},"unreads":{"1":2,"2":7}}"#;
+        let counters: FeedCounters = serde_json::from_str(json).unwrap();
+        assert_eq!(counters.reads[&3], 1);
+        assert_eq!(counters.unread(2), 7);
+        assert_eq!(counters.unread(3), 0);
+        assert_eq!(counters.total_unread(), 9);
+    }
+
+    #[test]
+    fn test_feed_creation_request_skips_unset_fields() {
+        let request = FeedCreationRequest {
+            crawler: Some(true),
+            ..FeedCreationRequest::new("https://example.org/feed.xml", 3)
+        };
+        let value = serde_json::to_value(&request).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "feed_url": "https://example.org/feed.xml",
+                "category_id": 3,
+                "crawler": true
+            })
+        );
+    }
+
+    #[test]
+    fn test_feed_modification_request_empty() {
+        let request = FeedModificationRequest::default();
+        assert_eq!(serde_json::to_string(&request).unwrap(), "{}");
+    }
+
+    #[test]
+    fn test_discovered_feed_best() {
+        let candidates: Vec<DiscoveredFeed> = serde_json::from_str(
+            r#"[
+                {"url":"https://example.org/feed.json","title":"JSON","type":"json"},
+                {"url":"https://example.org/rss.xml","title":"RSS","type":"rss"},
+                {"url":"https://example.org/atom.xml","title":"Atom","type":"atom"}
+            ]"#,
+        )
+        .unwrap();
+        let best = DiscoveredFeed::best(&candidates, "https://example.org/").unwrap();
+        assert_eq!(best.kind, "atom");
+        let exact = DiscoveredFeed::best(&candidates, "https://example.org/rss.xml").unwrap();
+        assert_eq!(exact.title, "RSS");
+        assert!(DiscoveredFeed::best(&[], "https://example.org/").is_none());
+    }
+
+    #[test]
+    fn test_debug_redacts_credentials() {
+        let feed = Feed {
+            id: 42,
+            cookie: "session=cookie-secret".to_string(),
+            username: "user-secret".to_string(),
+            password: "password-secret".to_string(),
+            ..Default::default()
+        };
+        let mut creation = FeedCreationRequest::new("https://example.org/feed.xml", 1);
+        creation.cookie = Some("session=cookie-secret".to_string());
+        creation.username = Some("user-secret".to_string());
+        creation.password = Some("password-secret".to_string());
+        let modification = FeedModificationRequest {
+            cookie: creation.cookie.clone(),
+            username: creation.username.clone(),
+            password: creation.password.clone(),
+            ..Default::default()
+        };
+        for output in [
+            format!("{:?}", feed),
+            format!("{:?}", creation),
+            format!("{:?}", modification),
+        ] {
+            assert!(!output.contains("secret"), "{}", output);
+            assert!(output.contains("password: "), "{}", output);
+        }
+        assert!(format!("{:?}", creation).contains("https://example.org/feed.xml"));
+        assert!(format!("{:?}", FeedCreationRequest::default()).contains("password: None"));
+    }
+}
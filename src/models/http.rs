@@ -0,0 +1,86 @@
+use reqwest::{Client, Proxy};
+use std::{env, time::Duration};
+
+const USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+
+#[derive(Debug, Clone)]
+pub struct HttpConfig {
+    pub timeout: Duration,
+    pub connect_timeout: Duration,
+    pub user_agent: String,
+    pub proxy: Option<String>,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        HttpConfig {
+            timeout: Duration::from_secs(120),
+            connect_timeout: Duration::from_secs(10),
+            user_agent: USER_AGENT.to_string(),
+            proxy: None,
+        }
+    }
+}
+
+impl HttpConfig {
+    pub fn from_env() -> Self {
+        let default = Self::default();
+        let seconds = |name: &str, default: Duration| {
+            env::var(name)
+                .ok()
+                .and_then(|value| value.parse::<u64>().ok())
+                .map(Duration::from_secs)
+                .unwrap_or(default)
+        };
+        HttpConfig {
+            timeout: seconds("HTTP_TIMEOUT", default.timeout),
+            connect_timeout: seconds("HTTP_CONNECT_TIMEOUT", default.connect_timeout),
+            user_agent: env::var("HTTP_USER_AGENT").unwrap_or(default.user_agent),
+            proxy: env::var("HTTP_PROXY_URL").ok().filter(|proxy| !proxy.is_empty()),
+        }
+    }
+
+    pub fn build(&self) -> Result<Client, reqwest::Error> {
+        let mut builder = Client::builder()
+            .timeout(self.timeout)
+            .connect_timeout(self.connect_timeout)
+            .user_agent(&self.user_agent);
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(Proxy::all(proxy)?);
+        }
+        builder.build()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::HttpConfig;
+    use std::time::Duration;
+
+    #[test]
+    fn test_http_config_default() {
+        let config = HttpConfig::default();
+        assert_eq!(config.timeout, Duration::from_secs(120));
+        assert!(config.user_agent.starts_with("miniflux-client/"));
+        assert!(config.proxy.is_none());
+        assert!(config.build().is_ok());
+    }
+
+    #[test]
+    fn test_http_config_with_proxy() {
+        let config = HttpConfig {
+            proxy: Some("http://127.0.0.1:3128".to_string()),
+            ..HttpConfig::default()
+        };
+        assert!(config.build().is_ok());
+    }
+
+    #[test]
+    fn test_http_config_with_invalid_proxy() {
+        let config = HttpConfig {
+            proxy: Some("not a url".to_string()),
+            ..HttpConfig::default()
+        };
+        assert!(config.build().is_err());
+    }
+}
@@ -0,0 +1,67 @@
+use std::{fs, io, path::PathBuf};
+use tracing::debug;
+
+use super::Icon;
+
+// Keeps feed icons, and the Matrix media URI they were uploaded to, across runs
+#[derive(Debug, Clone)]
+pub struct IconCache {
+    dir: PathBuf,
+}
+
+impl IconCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        IconCache { dir: dir.into() }
+    }
+
+    fn path(&self, icon_id: i64, extension: &str) -> PathBuf {
+        self.dir.join(format!("{}.{}", icon_id, extension))
+    }
+
+    pub fn get(&self, icon_id: i64) -> Option<Icon> {
+        let data = fs::read(self.path(icon_id, "json")).ok()?;
+        serde_json::from_slice(&data).ok()
+    }
+
+    pub fn put(&self, icon: &Icon) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        fs::write(self.path(icon.id, "json"), serde_json::to_vec(icon)?)?;
+        debug!("Icon {} cached", icon.id);
+        Ok(())
+    }
+
+    pub fn uri(&self, icon_id: i64) -> Option<String> {
+        let uri = fs::read_to_string(self.path(icon_id, "mxc")).ok()?;
+        let uri = uri.trim();
+        (!uri.is_empty()).then(|| uri.to_string())
+    }
+
+    pub fn put_uri(&self, icon_id: i64, uri: &str) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        fs::write(self.path(icon_id, "mxc"), uri)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::IconCache;
+    use crate::models::Icon;
+
+    #[test]
+    fn test_icon_cache_roundtrip() {
+        let dir = std::env::temp_dir().join(format!("miniflux-icons-{}", std::process::id()));
+        let cache = IconCache::new(&dir);
+        assert!(cache.get(7).is_none());
+        assert!(cache.uri(7).is_none());
+        let icon = Icon {
+            id: 7,
+            data: "image/png;base64,iVBORw0KGgo=".to_string(),
+            mime_type: "image/png".to_string(),
+        };
+        cache.put(&icon).unwrap();
+        cache.put_uri(7, "mxc://example.org/abc").unwrap();
+        assert_eq!(cache.get(7).unwrap().data, icon.data);
+        assert_eq!(cache.uri(7).as_deref(), Some("mxc://example.org/abc"));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
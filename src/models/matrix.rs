@@ -1,4 +1,5 @@
-use super::CustomError;
+use super::{notifier, Capabilities, CustomError, Icon, IconCache, Markup, Notification, Notifier, RetryPolicy, RetryRequest};
+use async_trait::async_trait;
 use reqwest::{
     header::{HeaderMap, HeaderName, HeaderValue},
     Client,
@@ -7,8 +8,7 @@ use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use std::str::FromStr;
 use std::time::{SystemTime, UNIX_EPOCH};
-use tracing::{debug, info};
-use urlencoding::encode;
+use tracing::{debug, info, warn};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct MatrixClient {
@@ -17,6 +17,12 @@ pub struct MatrixClient {
     room: String,
     #[serde(skip)]
     pub base_url: Option<String>,
+    #[serde(skip)]
+    client: Client,
+    #[serde(skip)]
+    retry: RetryPolicy,
+    #[serde(skip)]
+    icon_cache: Option<IconCache>,
 }
 
 impl MatrixClient {
@@ -43,6 +49,9 @@ impl MatrixClient {
             token,
             room,
             base_url: None,
+            client: Client::new(),
+            retry: RetryPolicy::default(),
+            icon_cache: None,
         }
     }
 
@@ -52,9 +61,28 @@ impl MatrixClient {
             token,
             room,
             base_url: Some(base_url),
+            client: Client::new(),
+            retry: RetryPolicy::default(),
+            icon_cache: None,
         }
     }
 
+    pub fn with_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    // Enables feed icons in the digest, uploaded once and remembered in the cache
+    pub fn with_icon_cache(mut self, cache: IconCache) -> Self {
+        self.icon_cache = Some(cache);
+        self
+    }
+
     fn get_base_url(&self) -> &str {
         self.base_url.as_deref().unwrap_or("https")
     }
@@ -91,15 +119,60 @@ impl MatrixClient {
             HeaderValue::from_str(&format!("Bearer {}", self.token)).unwrap(),
         );
         debug!("Header: {:?}", header_map);
-        Self::_put(&url, header_map, &body).await
+        self._put(&url, header_map, &body).await
     }
 
-    async fn _put(url: &str, header_map: HeaderMap, body: &Value) -> Result<String, CustomError> {
-        let client = Client::builder()
-            .default_headers(header_map)
-            .build()
-            .unwrap();
-        let response = client.put(url).json(body).send().await?;
+    // The homeserver answers with the `event_id` of the sent message
+    pub fn is_sent(response: &str) -> bool {
+        serde_json::from_str::<Value>(response)
+            .ok()
+            .and_then(|value| value.get("event_id").map(|id| id.is_string()))
+            .unwrap_or(false)
+    }
+
+    // Returns the mxc:// URI of the uploaded content
+    pub async fn upload_media(&self, data: Vec<u8>, mime_type: &str, filename: &str) -> Result<String, CustomError> {
+        let url = format!("{}://{}/_matrix/media/v3/upload", self.get_base_url(), self.server);
+        debug!("Uploading {} ({} bytes) to {}", filename, data.len(), url);
+        let response = self
+            .client
+            .post(url)
+            .query(&[("filename", filename)])
+            .bearer_auth(&self.token)
+            .header("Content-Type", mime_type)
+            .body(data)
+            .send_with(&self.retry)
+            .await?;
+        let status = response.status();
+        let body: Value = response.json().await?;
+        match body.get("content_uri").and_then(|uri| uri.as_str()) {
+            Some(uri) if status.is_success() => Ok(uri.to_string()),
+            _ => Err(format!("Matrix upload failed ({}): {}", status, body).into()),
+        }
+    }
+
+    // Uploads each icon only once, later calls reuse the cached URI
+    pub async fn icon_uri(&self, cache: &IconCache, icon: &Icon) -> Result<String, CustomError> {
+        if let Some(uri) = cache.uri(icon.id) {
+            return Ok(uri);
+        }
+        let extension = icon.mime_type.rsplit('/').next().unwrap_or("png");
+        let filename = format!("icon-{}.{}", icon.id, extension);
+        let uri = self.upload_media(icon.bytes()?, &icon.mime_type, &filename).await?;
+        if let Err(e) = cache.put_uri(icon.id, &uri) {
+            warn!("Unable to cache Matrix URI of icon {}: {}", icon.id, e);
+        }
+        Ok(uri)
+    }
+
+    async fn _put(&self, url: &str, header_map: HeaderMap, body: &Value) -> Result<String, CustomError> {
+        let response = self
+            .client
+            .put(url)
+            .headers(header_map)
+            .json(body)
+            .send_with(&self.retry)
+            .await?;
 
         let status = response.status();
         let response_body = response.text().await?;
@@ -125,9 +198,53 @@ impl MatrixClient {
     }
 }
 
+#[async_trait]
+impl Notifier for MatrixClient {
+    fn name(&self) -> &str {
+        "Matrix"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            // Events are limited to 64KiB, leave room for the plain body copy
+            max_length: 30_000,
+            markup: Markup::Html,
+            icons: self.icon_cache.is_some(),
+        }
+    }
+
+    async fn send(&self, message: &str) -> Result<(), CustomError> {
+        let response = self.post(message).await?;
+        debug!("Matrix response: {:?}", response);
+        match Self::is_sent(&response) {
+            true => Ok(()),
+            false => Err(format!("Matrix rejected the news: {}", response).into()),
+        }
+    }
+
+    async fn render(&self, item: &Notification) -> String {
+        let rendered = notifier::render(item, Markup::Html);
+        let (Some(cache), Some(icon)) = (&self.icon_cache, &item.icon) else {
+            return rendered;
+        };
+        match self.icon_uri(cache, icon).await {
+            Ok(uri) => rendered.replacen(
+                "<h3>",
+                &format!("<h3><img src=\"{}\" alt=\"\" width=\"16\" height=\"16\"> ", uri),
+                1,
+            ),
+            Err(e) => {
+                warn!("Unable to upload icon {}: {}", icon.id, e);
+                rendered
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use super::MatrixClient;
+    use super::{Icon, IconCache, MatrixClient, Notification, Notifier, RetryPolicy};
+    use std::time::Duration;
     use dotenv::dotenv;
     use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 
@@ -209,4 +326,131 @@ mod test {
         let ts2 = MatrixClient::ts();
         assert!(ts2 >= ts1);
     }
+
+    #[tokio::test]
+    async fn test_icon_uri_uploads_once() {
+        let mut server = mockito::Server::new_async().await;
+        let upload = server.mock("POST", "/_matrix/media/v3/upload")
+            .match_query(mockito::Matcher::UrlEncoded("filename".into(), "icon-3.png".into()))
+            .match_header("authorization", "Bearer token")
+            .match_header("content-type", "image/png")
+            .with_status(200)
+            .with_body(r#"{"content_uri":"mxc://example.org/icon3"}"#)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let client = MatrixClient::with_base_url(
+            server.host_with_port(),
+            "token".to_string(),
+            "room".to_string(),
+            "http".to_string(),
+        );
+        let dir = std::env::temp_dir().join(format!("matrix-icons-{}", std::process::id()));
+        let cache = IconCache::new(&dir);
+        let icon = Icon {
+            id: 3,
+            data: "image/png;base64,iVBORw0KGgo=".to_string(),
+            mime_type: "image/png".to_string(),
+        };
+
+        assert_eq!(client.icon_uri(&cache, &icon).await.unwrap(), "mxc://example.org/icon3");
+        assert_eq!(client.icon_uri(&cache, &icon).await.unwrap(), "mxc://example.org/icon3");
+        upload.assert_async().await;
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_upload_media_error() {
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server.mock("POST", "/_matrix/media/v3/upload")
+            .match_query(mockito::Matcher::Any)
+            .with_status(413)
+            .with_body(r#"{"errcode":"M_TOO_LARGE","error":"Too large"}"#)
+            .create_async()
+            .await;
+
+        let client = MatrixClient::with_base_url(
+            server.host_with_port(),
+            "token".to_string(),
+            "room".to_string(),
+            "http".to_string(),
+        );
+        let result = client.upload_media(vec![1, 2, 3], "image/png", "a.png").await;
+        assert!(result.unwrap_err().to_string().contains("M_TOO_LARGE"));
+    }
+
+    #[tokio::test]
+    async fn test_post_retries_when_rate_limited() {
+        let mut server = mockito::Server::new_async().await;
+        let limited = server.mock("PUT", mockito::Matcher::Regex("^/_matrix/client/v3/rooms/.+/send/m.room.message/.+$".to_string()))
+            .with_status(429)
+            .with_body(r#"{"errcode":"M_LIMIT_EXCEEDED","error":"Too many requests","retry_after_ms":1}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let sent = server.mock("PUT", mockito::Matcher::Regex("^/_matrix/client/v3/rooms/.+/send/m.room.message/.+$".to_string()))
+            .with_status(200)
+            .with_body(r#"{"event_id":"$abc"}"#)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let client = MatrixClient::with_base_url(
+            server.host_with_port(),
+            "token".to_string(),
+            "room".to_string(),
+            "http".to_string(),
+        )
+        .with_retry(RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(5),
+            jitter: 0.0,
+        });
+
+        let body = client.post("<p>Hello</p>").await.unwrap();
+        assert!(MatrixClient::is_sent(&body));
+        assert!(!MatrixClient::is_sent(r#"{"errcode":"M_FORBIDDEN"}"#));
+        limited.assert_async().await;
+        sent.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_render_with_icon() {
+        let mut server = mockito::Server::new_async().await;
+        let _upload = server.mock("POST", "/_matrix/media/v3/upload")
+            .match_query(mockito::Matcher::Any)
+            .with_status(200)
+            .with_body(r#"{"content_uri":"mxc://example.org/icon5"}"#)
+            .create_async()
+            .await;
+
+        let dir = std::env::temp_dir().join(format!("matrix-render-{}", std::process::id()));
+        let client = MatrixClient::with_base_url(
+            server.host_with_port(),
+            "token".to_string(),
+            "room".to_string(),
+            "http".to_string(),
+        )
+        .with_icon_cache(IconCache::new(&dir));
+        let item = Notification {
+            title: "Title".to_string(),
+            url: "https://example.org".to_string(),
+            summary: "Summary".to_string(),
+            icon: Some(Icon {
+                id: 5,
+                data: "image/png;base64,iVBORw0KGgo=".to_string(),
+                mime_type: "image/png".to_string(),
+            }),
+            ..Notification::default()
+        };
+
+        assert!(client.capabilities().icons);
+        assert_eq!(
+            client.render(&item).await,
+            "<h3><img src=\"mxc://example.org/icon5\" alt=\"\" width=\"16\" height=\"16\"> <a href=\"https://example.org\">Title</a></h3><p>Summary</p><br>"
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
 }
@@ -1,191 +1,636 @@
-use serde::{Serialize, Deserialize};
-use reqwest::Client;
+use futures::{stream, Stream, TryStreamExt};
+use serde::{de::DeserializeOwned, Serialize, Deserialize};
+use reqwest::{Client, Method, RequestBuilder, Response};
 use serde_json::Value;
-use tracing::debug;
+use tracing::{debug, warn};
+
+use super::{
+    Auth, Category, Direction, DiscoveredFeed, Enclosure, EntriesResponse, Entry, EntryOrder, EntryQuery, EntryStatus, Feed,
+    FeedCounters, FeedCreationRequest, FeedModificationRequest, Icon, IconCache, MinifluxError, Opml, OpmlDiff, User,
+    RetryPolicy, RetryRequest, UserCreationRequest, UserModificationRequest, VersionInfo,
+};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MinifluxClient {
+    // Either a host (`rss.example.com`) or a full base URL (`https://example.com/miniflux/`)
     pub url: String,
-    pub token: String,
+    #[serde(flatten)]
+    pub auth: Auth,
     #[serde(skip)]
     pub base_url: Option<String>,
+    #[serde(skip)]
+    client: Client,
+    #[serde(skip)]
+    retry: RetryPolicy,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 struct Data {
-    entry_ids: Vec<u64>,
-    status: String
+    entry_ids: Vec<i64>,
+    status: EntryStatus,
+}
+
+#[derive(Debug, Serialize)]
+struct EntryUpdate<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content: Option<&'a str>,
+}
+
+#[derive(Debug, Serialize)]
+struct EnclosureUpdate {
+    media_progression: i64,
+}
+
+#[derive(Debug, Serialize)]
+struct CategoryData<'a> {
+    title: &'a str,
 }
 
 #[derive(Debug, Serialize)]
-struct OneItem {
-    status: String
+struct DiscoverData<'a> {
+    url: &'a str,
+}
+
+#[derive(Debug, Deserialize)]
+struct FeedCreated {
+    feed_id: i64,
 }
 
 impl MinifluxClient {
 
     pub fn new(url: String, token: String) -> Self {
+        Self::with_auth(url, Auth::Token { token })
+    }
+
+    pub fn with_auth(url: String, auth: Auth) -> Self {
         MinifluxClient {
             url,
-            token,
+            auth,
             base_url: None,
+            client: Client::new(),
+            retry: RetryPolicy::default(),
         }
     }
 
     pub fn with_base_url(url: String, token: String, base_url: String) -> Self {
         MinifluxClient {
             url,
-            token,
+            auth: Auth::Token { token },
             base_url: Some(base_url),
+            client: Client::new(),
+            retry: RetryPolicy::default(),
         }
     }
 
+    pub fn with_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
     fn get_base_url(&self) -> &str {
         self.base_url.as_deref().unwrap_or("https")
     }
 
-    pub async fn get_categories(&self) -> Result<Vec<Value>, Box<dyn std::error::Error>> {
-        let url = format!("{}://{}/v1/categories", self.get_base_url(), self.url);
-        let client = Client::new();
-        let response = client
-            .get(&url)
-            .header("X-Auth-Token", &self.token)
-            .send()
-            .await?;
-        
-        let status = response.status();
-        if !status.is_success() {
-            let error_body = response.text().await.unwrap_or_else(|_| "Unable to read error body".to_string());
-            debug!("Miniflux API error (get_categories) - Status: {}, Body: {}", status, error_body);
-            return Err(format!("Miniflux API error: {}", error_body).into());
+    fn endpoint(&self, path: &str) -> String {
+        let root = self.url.trim_end_matches('/');
+        if root.contains("://") {
+            format!("{}/{}", root, path)
+        } else {
+            format!("{}://{}/{}", self.get_base_url(), root, path)
         }
-        
-        let content = response.json::<serde_json::Value>().await?;
-        Ok(content.as_array().unwrap().to_vec())
     }
 
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let url = self.endpoint(&format!("v1/{}", path));
+        self.auth.apply(self.client.request(method, &url))
+    }
 
-    pub async fn get_category_entries(&self, category_id: i32) -> Result<Vec<Value>, Box<dyn std::error::Error>> {
-        let url = format!("{}://{}/v1/categories/{}/entries", self.get_base_url(), self.url, category_id);
-        let client = Client::new();
-        let response = client
-            .get(&url)
-            .query(&[("status", "unread")])
-            .header("X-Auth-Token", &self.token)
-            .send()
-            .await?;
-        
+    async fn check(response: Response, context: &str) -> Result<Response, MinifluxError> {
         let status = response.status();
-        if !status.is_success() {
-            let error_body = response.text().await.unwrap_or_else(|_| "Unable to read error body".to_string());
-            debug!("Miniflux API error (get_category_entries) - Status: {}, Body: {}", status, error_body);
-            return Err(format!("Miniflux API error: {}", error_body).into());
+        if status.is_success() {
+            return Ok(response);
         }
-        
-        let content = response.json::<serde_json::Value>().await?;
-        Ok(content["entries"].as_array().unwrap().to_vec())
-    }
-
-    pub async fn get_entries(&self, limit: usize) -> Result<Vec<Value>, Box<dyn std::error::Error>> {
-        let url = format!("{}://{}/v1/entries", self.get_base_url(), self.url);
-        let client = Client::new();
-        let response = client
-            .get(&url)
-            .query(&[
-                ("status", "unread"),
-                ("limit", &limit.to_string()),
-                ("order", "published_at"),
-                ("direction", "asc"),
-                ])
-            .header("X-Auth-Token", &self.token)
-            .send()
+        let error_body = response.text().await.unwrap_or_else(|_| "Unable to read error body".to_string());
+        debug!("Miniflux API error ({}) - Status: {}, Body: {}", context, status, error_body);
+        Err(MinifluxError::from_response(status, &error_body))
+    }
+
+    async fn decode<T: DeserializeOwned>(response: Response, context: &str) -> Result<T, MinifluxError> {
+        let response = Self::check(response, context).await?;
+        let body = response.bytes().await?;
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    // Does not need credentials, only tells whether the server is up
+    pub async fn healthcheck(&self) -> Result<(), MinifluxError> {
+        let response = self.client.get(self.endpoint("healthcheck")).send_with(&self.retry).await?;
+        let response = Self::check(response, "healthcheck").await?;
+        let body = response.text().await?;
+        match body.trim() {
+            "OK" => Ok(()),
+            other => Err(MinifluxError::Unhealthy(other.to_string())),
+        }
+    }
+
+    pub async fn version(&self) -> Result<VersionInfo, MinifluxError> {
+        let response = self.request(Method::GET, "version").send_with(&self.retry).await?;
+        Self::decode(response, "version").await
+    }
+
+    // Cheapest authenticated call, used to validate the token
+    pub async fn me(&self) -> Result<User, MinifluxError> {
+        let response = self.request(Method::GET, "me").send_with(&self.retry).await?;
+        Self::decode(response, "me").await
+    }
+
+    pub async fn list_users(&self) -> Result<Vec<User>, MinifluxError> {
+        let response = self.request(Method::GET, "users").send_with(&self.retry).await?;
+        Self::decode(response, "list_users").await
+    }
+
+    pub async fn get_user(&self, user_id: i64) -> Result<User, MinifluxError> {
+        let response = self
+            .request(Method::GET, &format!("users/{}", user_id))
+            .send_with(&self.retry)
             .await?;
-        
-        let status = response.status();
-        if !status.is_success() {
-            let error_body = response.text().await.unwrap_or_else(|_| "Unable to read error body".to_string());
-            debug!("Miniflux API error (get_entries) - Status: {}, Body: {}", status, error_body);
-            return Err(format!("Miniflux API error: {}", error_body).into());
+        Self::decode(response, "get_user").await
+    }
+
+    pub async fn get_user_by_username(&self, username: &str) -> Result<User, MinifluxError> {
+        let response = self
+            .request(Method::GET, &format!("users/{}", urlencoding::encode(username)))
+            .send_with(&self.retry)
+            .await?;
+        Self::decode(response, "get_user_by_username").await
+    }
+
+    pub async fn create_user(&self, user: &UserCreationRequest) -> Result<User, MinifluxError> {
+        let response = self.request(Method::POST, "users").json(user).send_with(&self.retry).await?;
+        Self::decode(response, "create_user").await
+    }
+
+    pub async fn update_user(&self, user_id: i64, changes: &UserModificationRequest) -> Result<User, MinifluxError> {
+        let response = self
+            .request(Method::PUT, &format!("users/{}", user_id))
+            .json(changes)
+            .send_with(&self.retry)
+            .await?;
+        Self::decode(response, "update_user").await
+    }
+
+    pub async fn delete_user(&self, user_id: i64) -> Result<(), MinifluxError> {
+        let response = self
+            .request(Method::DELETE, &format!("users/{}", user_id))
+            .send_with(&self.retry)
+            .await?;
+        Self::check(response, "delete_user").await?;
+        debug!("User {} deleted", user_id);
+        Ok(())
+    }
+
+    pub async fn feed_counters(&self) -> Result<FeedCounters, MinifluxError> {
+        let response = self.request(Method::GET, "feeds/counters").send_with(&self.retry).await?;
+        Self::decode(response, "feed_counters").await
+    }
+
+    pub async fn get_categories(&self) -> Result<Vec<Category>, MinifluxError> {
+        let response = self.request(Method::GET, "categories").send_with(&self.retry).await?;
+        Self::decode(response, "get_categories").await
+    }
+
+
+    pub async fn create_category(&self, title: &str) -> Result<Category, MinifluxError> {
+        let response = self
+            .request(Method::POST, "categories")
+            .json(&CategoryData { title })
+            .send_with(&self.retry)
+            .await?;
+        Self::decode(response, "create_category").await
+    }
+
+    pub async fn update_category(&self, category_id: i64, title: &str) -> Result<Category, MinifluxError> {
+        let response = self
+            .request(Method::PUT, &format!("categories/{}", category_id))
+            .json(&CategoryData { title })
+            .send_with(&self.retry)
+            .await?;
+        Self::decode(response, "update_category").await
+    }
+
+    pub async fn delete_category(&self, category_id: i64) -> Result<(), MinifluxError> {
+        let response = self
+            .request(Method::DELETE, &format!("categories/{}", category_id))
+            .send_with(&self.retry)
+            .await?;
+        Self::check(response, "delete_category").await?;
+        debug!("Category {} deleted", category_id);
+        Ok(())
+    }
+
+    pub async fn get_category_feeds(&self, category_id: i64) -> Result<Vec<Feed>, MinifluxError> {
+        let response = self
+            .request(Method::GET, &format!("categories/{}/feeds", category_id))
+            .send_with(&self.retry)
+            .await?;
+        Self::decode(response, "get_category_feeds").await
+    }
+
+    pub async fn refresh_category(&self, category_id: i64) -> Result<(), MinifluxError> {
+        let response = self
+            .request(Method::PUT, &format!("categories/{}/refresh", category_id))
+            .send_with(&self.retry)
+            .await?;
+        Self::check(response, "refresh_category").await?;
+        debug!("Category {} refreshed successfully", category_id);
+        Ok(())
+    }
+
+    pub async fn mark_category_as_read(&self, category_id: i64) -> Result<(), MinifluxError> {
+        self.mark_all_as_read(&format!("categories/{}/mark-all-as-read", category_id)).await
+    }
+
+    pub async fn mark_feed_as_read(&self, feed_id: i64) -> Result<(), MinifluxError> {
+        self.mark_all_as_read(&format!("feeds/{}/mark-all-as-read", feed_id)).await
+    }
+
+    pub async fn mark_user_as_read(&self, user_id: i64) -> Result<(), MinifluxError> {
+        self.mark_all_as_read(&format!("users/{}/mark-all-as-read", user_id)).await
+    }
+
+    async fn mark_all_as_read(&self, path: &str) -> Result<(), MinifluxError> {
+        let response = self.request(Method::PUT, path).send_with(&self.retry).await?;
+        Self::check(response, path).await?;
+        debug!("All entries marked as read ({})", path);
+        Ok(())
+    }
+
+    async fn find_or_create_category(&self, categories: &mut Vec<Category>, title: &str) -> Result<i64, MinifluxError> {
+        if let Some(category) = categories.iter().find(|c| c.title.eq_ignore_ascii_case(title)) {
+            return Ok(category.id);
         }
-        
-        let content = response.json::<serde_json::Value>().await?;
-        Ok(content["entries"].as_array().unwrap().to_vec())
+        let category = self.create_category(title).await?;
+        let id = category.id;
+        categories.push(category);
+        Ok(id)
+    }
+
+    pub async fn get_category_entries(&self, category_id: i64) -> Result<Vec<Entry>, MinifluxError> {
+        let query = EntryQuery::unread();
+        Ok(self.get_category_entries_page(category_id, &query).await?.entries)
+    }
+
+    pub async fn get_entries(&self, limit: usize) -> Result<Vec<Entry>, MinifluxError> {
+        let query = EntryQuery::unread()
+            .limit(limit)
+            .order(EntryOrder::PublishedAt)
+            .direction(Direction::Asc);
+        Ok(self.get_entries_page(&query).await?.entries)
+    }
+
+    pub async fn get_entries_page(&self, query: &EntryQuery) -> Result<EntriesResponse, MinifluxError> {
+        self.fetch_page("entries", query).await
     }
 
-    pub async fn refresh_all_feeds(&self) -> Result<(), Box<dyn std::error::Error>> {
-        let url = format!("{}://{}/v1/feeds/refresh", self.get_base_url(), self.url);
-        let client = Client::new();
-        let response = client
-            .put(&url)
-            .header("X-Auth-Token", &self.token)
-            .send()
+    pub async fn get_category_entries_page(&self, category_id: i64, query: &EntryQuery) -> Result<EntriesResponse, MinifluxError> {
+        self.fetch_page(&format!("categories/{}/entries", category_id), query).await
+    }
+
+    pub fn entries_stream(&self, query: EntryQuery) -> impl Stream<Item = Result<Entry, MinifluxError>> + '_ {
+        self.paginate("entries".to_string(), query)
+    }
+
+    pub fn category_entries_stream(&self, category_id: i64, query: EntryQuery) -> impl Stream<Item = Result<Entry, MinifluxError>> + '_ {
+        self.paginate(format!("categories/{}/entries", category_id), query)
+    }
+
+    async fn fetch_page(&self, path: &str, query: &EntryQuery) -> Result<EntriesResponse, MinifluxError> {
+        debug!("Fetching {} with {:?}", path, query);
+        let response = self
+            .request(Method::GET, path)
+            .query(&query.to_query())
+            .send_with(&self.retry)
             .await?;
-        
-        let status = response.status();
-        if !status.is_success() {
-            let error_body = response.text().await.unwrap_or_else(|_| "Unable to read error body".to_string());
-            debug!("Miniflux API error (refresh_all_feeds) - Status: {}, Body: {}", status, error_body);
-            return Err(format!("Miniflux API error: {}", error_body).into());
-        } else {
-            debug!("All feeds refreshed successfully");
+        Self::decode(response, path).await
+    }
+
+    // Walks every page of `path`, using `after_entry_id` (or `before_entry_id`
+    // when descending) as a cursor when the order allows it and falling back
+    // to offsets otherwise
+    fn paginate(&self, path: String, query: EntryQuery) -> impl Stream<Item = Result<Entry, MinifluxError>> + '_ {
+        let mut query = query;
+        let page_size = query.page_size();
+        query.limit = Some(page_size);
+        let cursor = query.uses_cursor();
+        let descending = query.direction == Some(Direction::Desc);
+        if cursor {
+            query.order = Some(EntryOrder::Id);
+            query.direction = Some(if descending { Direction::Desc } else { Direction::Asc });
+            // The cursor already skips what was read, an offset would skip more
+            query.offset = None;
         }
+        stream::try_unfold((query, 0usize, false), move |(mut query, fetched, done)| {
+            let path = path.clone();
+            async move {
+                if done {
+                    return Ok::<_, MinifluxError>(None);
+                }
+                let page = self.fetch_page(&path, &query).await?;
+                let count = page.entries.len();
+                if count == 0 {
+                    return Ok(None);
+                }
+                let fetched = fetched + count;
+                let done = count < page_size || (!cursor && fetched as i64 >= page.total);
+                if cursor && descending {
+                    query.before_entry_id = page.entries.last().map(|entry| entry.id);
+                } else if cursor {
+                    query.after_entry_id = page.entries.last().map(|entry| entry.id);
+                } else {
+                    query.offset = Some(query.offset.unwrap_or(0) + count);
+                }
+                let entries = stream::iter(page.entries.into_iter().map(Ok));
+                Ok(Some((entries, (query, fetched, done))))
+            }
+        })
+        .try_flatten()
+    }
+
+    pub async fn refresh_all_feeds(&self) -> Result<(), MinifluxError> {
+        let response = self.request(Method::PUT, "feeds/refresh").send_with(&self.retry).await?;
+        Self::check(response, "refresh_all_feeds").await?;
+        debug!("All feeds refreshed successfully");
         Ok(())
     }
 
-    pub async fn get_content(&self, entry_id: u64) -> Result<String, Box<dyn std::error::Error>> {
-        let url = format!("{}://{}/v1/entries/{}/fetch-content", self.get_base_url(), self.url, entry_id);
-        let client = Client::new();
-        let response = client
-            .get(&url)
-            .header("X-Auth-Token", &self.token)
-            .send()
+    pub async fn list_feeds(&self) -> Result<Vec<Feed>, MinifluxError> {
+        let response = self.request(Method::GET, "feeds").send_with(&self.retry).await?;
+        Self::decode(response, "list_feeds").await
+    }
+
+    pub async fn get_feed(&self, feed_id: i64) -> Result<Feed, MinifluxError> {
+        let response = self
+            .request(Method::GET, &format!("feeds/{}", feed_id))
+            .send_with(&self.retry)
             .await?;
-        
-        let status = response.status();
-        if !status.is_success() {
-            let error_body = response.text().await.unwrap_or_else(|_| "Unable to read error body".to_string());
-            debug!("Miniflux API error (get_content) - Status: {}, Body: {}", status, error_body);
-            return Err(format!("Miniflux API error: {}", error_body).into());
+        Self::decode(response, "get_feed").await
+    }
+
+    pub async fn create_feed(&self, feed: &FeedCreationRequest) -> Result<i64, MinifluxError> {
+        debug!("Creating feed {} in category {}", feed.feed_url, feed.category_id);
+        let response = self
+            .request(Method::POST, "feeds")
+            .json(feed)
+            .send_with(&self.retry)
+            .await?;
+        let created: FeedCreated = Self::decode(response, "create_feed").await?;
+        Ok(created.feed_id)
+    }
+
+    pub async fn update_feed(&self, feed_id: i64, changes: &FeedModificationRequest) -> Result<Feed, MinifluxError> {
+        debug!("Updating feed {}", feed_id);
+        let response = self
+            .request(Method::PUT, &format!("feeds/{}", feed_id))
+            .json(changes)
+            .send_with(&self.retry)
+            .await?;
+        Self::decode(response, "update_feed").await
+    }
+
+    pub async fn delete_feed(&self, feed_id: i64) -> Result<(), MinifluxError> {
+        let response = self
+            .request(Method::DELETE, &format!("feeds/{}", feed_id))
+            .send_with(&self.retry)
+            .await?;
+        Self::check(response, "delete_feed").await?;
+        debug!("Feed {} deleted", feed_id);
+        Ok(())
+    }
+
+    pub async fn refresh_feed(&self, feed_id: i64) -> Result<(), MinifluxError> {
+        let response = self
+            .request(Method::PUT, &format!("feeds/{}/refresh", feed_id))
+            .send_with(&self.retry)
+            .await?;
+        Self::check(response, "refresh_feed").await?;
+        debug!("Feed {} refreshed successfully", feed_id);
+        Ok(())
+    }
+
+    pub async fn feed_entries(&self, feed_id: i64, query: &EntryQuery) -> Result<EntriesResponse, MinifluxError> {
+        self.fetch_page(&format!("feeds/{}/entries", feed_id), query).await
+    }
+
+    pub fn feed_entries_stream(&self, feed_id: i64, query: EntryQuery) -> impl Stream<Item = Result<Entry, MinifluxError>> + '_ {
+        self.paginate(format!("feeds/{}/entries", feed_id), query)
+    }
+
+    pub async fn get_feed_icon(&self, feed_id: i64) -> Result<Icon, MinifluxError> {
+        let response = self
+            .request(Method::GET, &format!("feeds/{}/icon", feed_id))
+            .send_with(&self.retry)
+            .await?;
+        Self::decode(response, "get_feed_icon").await
+    }
+
+    pub async fn get_icon(&self, icon_id: i64) -> Result<Icon, MinifluxError> {
+        let response = self
+            .request(Method::GET, &format!("icons/{}", icon_id))
+            .send_with(&self.retry)
+            .await?;
+        Self::decode(response, "get_icon").await
+    }
+
+    pub async fn cached_icon(&self, cache: &IconCache, icon_id: i64) -> Result<Icon, MinifluxError> {
+        if let Some(icon) = cache.get(icon_id) {
+            return Ok(icon);
         }
-        
-        let content = response.json::<serde_json::Value>().await?;
-        Ok(content["content"].as_str().unwrap().to_string())
+        let icon = self.get_icon(icon_id).await?;
+        if let Err(e) = cache.put(&icon) {
+            warn!("Unable to cache icon {}: {}", icon_id, e);
+        }
+        Ok(icon)
+    }
+
+    pub async fn discover(&self, url: &str) -> Result<Vec<DiscoveredFeed>, MinifluxError> {
+        let response = self
+            .request(Method::POST, "discover")
+            .json(&DiscoverData { url })
+            .send_with(&self.retry)
+            .await?;
+        Self::decode(response, "discover").await
     }
 
-    pub async fn mark_as_read(&self, entry_id: u64) -> Result<(), Box<dyn std::error::Error>> {
+    // Finds the feeds behind a website URL, picks the best candidate and
+    // subscribes to it in `category` (created if needed). Returns the feed id.
+    pub async fn subscribe(&self, url: &str, category: &str) -> Result<i64, MinifluxError> {
+        let candidates = self.discover(url).await?;
+        debug!("Discovered feeds for {}: {:?}", url, candidates);
+        let feed = DiscoveredFeed::best(&candidates, url)
+            .ok_or_else(|| MinifluxError::NoFeedFound(url.to_string()))?;
+        let mut categories = self.get_categories().await?;
+        let category_id = self.find_or_create_category(&mut categories, category).await?;
+        self.create_feed(&FeedCreationRequest::new(&feed.url, category_id)).await
+    }
+
+    pub async fn export_opml(&self) -> Result<String, MinifluxError> {
+        let response = self.request(Method::GET, "export").send_with(&self.retry).await?;
+        let response = Self::check(response, "export_opml").await?;
+        Ok(response.text().await?)
+    }
+
+    pub async fn import_opml(&self, opml: Vec<u8>) -> Result<(), MinifluxError> {
+        let response = self
+            .request(Method::POST, "import")
+            .header("content-type", "application/xml")
+            .body(opml)
+            .send_with(&self.retry)
+            .await?;
+        Self::check(response, "import_opml").await?;
+        debug!("OPML imported successfully");
+        Ok(())
+    }
+
+    // Subscribes, moves and (when `prune` is set) unsubscribes feeds until the
+    // account matches `desired`. Returns the changes that were applied.
+    pub async fn apply_opml(&self, desired: &Opml, prune: bool) -> Result<OpmlDiff, MinifluxError> {
+        let feeds = self.list_feeds().await?;
+        let mut categories = self.get_categories().await?;
+        let diff = Opml::from_feeds("Miniflux", &feeds).diff(desired);
+        debug!("OPML diff: {:?}", diff);
+        for feed in diff.added.iter().chain(diff.moved.iter()) {
+            let category_id = self.find_or_create_category(&mut categories, feed.category_title()).await?;
+            match feeds.iter().find(|existing| existing.feed_url == feed.xml_url) {
+                Some(existing) => {
+                    let changes = FeedModificationRequest {
+                        category_id: Some(category_id),
+                        ..Default::default()
+                    };
+                    self.update_feed(existing.id, &changes).await?;
+                }
+                None => {
+                    let request = FeedCreationRequest::new(&feed.xml_url, category_id);
+                    self.create_feed(&request).await?;
+                }
+            }
+        }
+        if prune {
+            for feed in diff.removed.iter() {
+                if let Some(existing) = feeds.iter().find(|existing| existing.feed_url == feed.xml_url) {
+                    self.delete_feed(existing.id).await?;
+                }
+            }
+        }
+        Ok(diff)
+    }
+
+    pub async fn get_content(&self, entry_id: i64) -> Result<String, MinifluxError> {
+        let response = self
+            .request(Method::GET, &format!("entries/{}/fetch-content", entry_id))
+            .send_with(&self.retry)
+            .await?;
+        let content: Value = Self::decode(response, "get_content").await?;
+        Ok(content["content"].as_str().unwrap_or_default().to_string())
+    }
+
+    pub async fn mark_as_read(&self, entry_id: i64) -> Result<(), MinifluxError> {
         self.mark_as_read_some(vec![entry_id]).await
     }
 
-    pub async fn mark_as_read_some(&self, entry_ids: Vec<u64>) -> Result<(), Box<dyn std::error::Error>> {
-        let url = format!("{}://{}/v1/entries", self.get_base_url(), self.url);
-        let client = Client::new();
+    pub async fn mark_as_read_some(&self, entry_ids: Vec<i64>) -> Result<(), MinifluxError> {
+        self.set_status(entry_ids, EntryStatus::Read).await
+    }
+
+    pub async fn set_status(&self, entry_ids: Vec<i64>, status: EntryStatus) -> Result<(), MinifluxError> {
         let data = Data {
             entry_ids,
-            status: "read".to_string(),
+            status,
         };
-        debug!("Marking entries as read: {:?}", data);
-        let response = client
-            .put(&url)
-            .header("X-Auth-Token", &self.token)
+        debug!("Changing entries status: {:?}", data);
+        let response = self
+            .request(Method::PUT, "entries")
             .json(&data)
-            .send()
+            .send_with(&self.retry)
             .await?;
-        
-        let status = response.status();
-        if !status.is_success() {
-            let error_body = response.text().await.unwrap_or_else(|_| "Unable to read error body".to_string());
-            debug!("Miniflux API error (mark_as_read) - Status: {}, Body: {}", status, error_body);
-            return Err(format!("Miniflux API error: {}", error_body).into());
-        } else {
-            debug!("Entries marked as read successfully");
-        }
+        Self::check(response, "set_status").await?;
+        debug!("Entries marked as {} successfully", status.as_str());
+        Ok(())
+    }
+
+    pub async fn get_entry(&self, entry_id: i64) -> Result<Entry, MinifluxError> {
+        let response = self
+            .request(Method::GET, &format!("entries/{}", entry_id))
+            .send_with(&self.retry)
+            .await?;
+        Self::decode(response, "get_entry").await
+    }
+
+    pub async fn toggle_bookmark(&self, entry_id: i64) -> Result<(), MinifluxError> {
+        let response = self
+            .request(Method::PUT, &format!("entries/{}/bookmark", entry_id))
+            .send_with(&self.retry)
+            .await?;
+        Self::check(response, "toggle_bookmark").await?;
+        debug!("Entry {} bookmark toggled", entry_id);
+        Ok(())
+    }
+
+    pub async fn update_entry(&self, entry_id: i64, title: Option<&str>, content: Option<&str>) -> Result<Entry, MinifluxError> {
+        let response = self
+            .request(Method::PUT, &format!("entries/{}", entry_id))
+            .json(&EntryUpdate { title, content })
+            .send_with(&self.retry)
+            .await?;
+        Self::decode(response, "update_entry").await
+    }
+
+    // Sends the entry to the third-party integrations enabled in Miniflux
+    pub async fn save_entry(&self, entry_id: i64) -> Result<(), MinifluxError> {
+        let response = self
+            .request(Method::POST, &format!("entries/{}/save", entry_id))
+            .send_with(&self.retry)
+            .await?;
+        Self::check(response, "save_entry").await?;
+        debug!("Entry {} saved to integrations", entry_id);
+        Ok(())
+    }
+
+    pub async fn get_enclosure(&self, enclosure_id: i64) -> Result<Enclosure, MinifluxError> {
+        let response = self
+            .request(Method::GET, &format!("enclosures/{}", enclosure_id))
+            .send_with(&self.retry)
+            .await?;
+        Self::decode(response, "get_enclosure").await
+    }
+
+    // Stores the playback position, in seconds, of an audio or video enclosure
+    pub async fn update_enclosure(&self, enclosure_id: i64, media_progression: i64) -> Result<(), MinifluxError> {
+        let response = self
+            .request(Method::PUT, &format!("enclosures/{}", enclosure_id))
+            .json(&EnclosureUpdate { media_progression })
+            .send_with(&self.retry)
+            .await?;
+        Self::check(response, "update_enclosure").await?;
+        debug!("Enclosure {} progression set to {}", enclosure_id, media_progression);
         Ok(())
     }
 }
 
 #[cfg(test)]
 mod test {
-    use super::MinifluxClient;
+    use super::{
+        Auth, Direction, EntryOrder, EntryQuery, EntryStatus, FeedCreationRequest,
+        FeedModificationRequest, MinifluxClient, MinifluxError, Opml, UserCreationRequest,
+        RetryPolicy, UserModificationRequest,
+    };
+    use std::time::Duration;
+    use futures::TryStreamExt;
     use dotenv::dotenv;
     use tracing::debug;
 
@@ -227,7 +672,7 @@ mod test {
         );
         let categories = miniflux.get_categories().await;
         println!("Categories: {:?}", categories);
-        let category_id = categories.unwrap().first().unwrap().as_object().unwrap().get("id").unwrap().as_i64().unwrap() as i32;
+        let category_id = categories.unwrap().first().unwrap().id;
         let entries = miniflux.get_category_entries(category_id).await;
         println!("Entries: {:?}", entries);
         debug!("Entries: {:?}", entries);
@@ -240,7 +685,7 @@ mod test {
         let token = "test_token".to_string();
         let client = MinifluxClient::new(url.clone(), token.clone());
         assert_eq!(client.url, url);
-        assert_eq!(client.token, token);
+        assert_eq!(client.auth, Auth::token(&token));
     }
 
     #[test]
@@ -248,7 +693,7 @@ mod test {
         let client = MinifluxClient::new("example.com".to_string(), "token".to_string());
         let cloned = client.clone();
         assert_eq!(client.url, cloned.url);
-        assert_eq!(client.token, cloned.token);
+        assert_eq!(client.auth, cloned.auth);
     }
 
     #[test]
@@ -264,7 +709,7 @@ mod test {
         let json = r#"{"url":"example.com","token":"token123"}"#;
         let client: MinifluxClient = serde_json::from_str(json).unwrap();
         assert_eq!(client.url, "example.com");
-        assert_eq!(client.token, "token123");
+        assert_eq!(client.auth, Auth::token("token123"));
     }
 
     // Tests con mocks
@@ -294,7 +739,8 @@ mod test {
         assert!(result.is_ok());
         let entries = result.unwrap();
         assert_eq!(entries.len(), 1);
-        assert_eq!(entries[0]["id"].as_i64().unwrap(), 1);
+        assert_eq!(entries[0].id, 1);
+        assert_eq!(entries[0].title, "Test Entry");
     }
 
     #[tokio::test]
@@ -318,7 +764,7 @@ mod test {
         assert!(result.is_ok());
         let categories = result.unwrap();
         assert_eq!(categories.len(), 2);
-        assert_eq!(categories[0]["title"].as_str().unwrap(), "Tech");
+        assert_eq!(categories[0].title, "Tech");
     }
 
     #[tokio::test]
@@ -348,6 +794,7 @@ mod test {
     async fn test_get_entries_unauthorized_error() {
         let mut server = mockito::Server::new_async().await;
         let _mock = server.mock("GET", "/v1/entries")
+            .match_query(mockito::Matcher::Any)
             .with_status(401)
             .with_header("content-type", "application/json")
             .with_body(r#"{"error_message":"access unauthorized"}"#)
@@ -363,10 +810,964 @@ mod test {
         
         // Verifica que retorne un error cuando el servidor responde con 401
         assert!(result.is_err());
-        let error_msg = result.unwrap_err().to_string();
+        let error = result.unwrap_err();
+        assert!(error.is_unauthorized());
+        let error_msg = error.to_string();
         assert!(error_msg.contains("Miniflux API error"));
+        assert!(error_msg.contains("access unauthorized"));
     }
 
-}
+    #[tokio::test]
+    async fn test_get_categories_server_error() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server.mock("GET", "/v1/categories")
+            .with_status(502)
+            .with_body("Bad Gateway")
+            .expect(1)
+            .create_async()
+            .await;
+
+        let client = MinifluxClient::with_base_url(
+            server.host_with_port(),
+            "test_token".to_string(),
+            "http".to_string(),
+        )
+        .with_retry(RetryPolicy::none());
+        let error = client.get_categories().await.unwrap_err();
+
+        assert_eq!(error.status(), Some(reqwest::StatusCode::BAD_GATEWAY));
+        assert!(error.is_retryable());
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_get_entries_decode_error() {
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server.mock("GET", "/v1/entries")
+            .match_query(mockito::Matcher::Any)
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body("<html>not json</html>")
+            .create_async()
+            .await;
+
+        let client = MinifluxClient::with_base_url(
+            server.host_with_port(),
+            "test_token".to_string(),
+            "http".to_string(),
+        );
+        let error = client.get_entries(10).await.unwrap_err();
+
+        assert!(matches!(error, MinifluxError::Decode(_)));
+    }
+
+    #[tokio::test]
+    async fn test_get_entries_page_with_mock() {
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server.mock("GET", "/v1/categories/3/entries")
+            .match_query(mockito::Matcher::AllOf(vec![
+                mockito::Matcher::UrlEncoded("status".into(), "unread".into()),
+                mockito::Matcher::UrlEncoded("limit".into(), "2".into()),
+                mockito::Matcher::UrlEncoded("offset".into(), "4".into()),
+            ]))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"total":5,"entries":[{"id":5}]}"#)
+            .create_async()
+            .await;
 
+        let client = MinifluxClient::with_base_url(
+            server.host_with_port(),
+            "test_token".to_string(),
+            "http".to_string(),
+        );
+        let query = EntryQuery::unread().limit(2).offset(4);
+        let page = client.get_category_entries_page(3, &query).await.unwrap();
+
+        assert_eq!(page.total, 5);
+        assert_eq!(page.entries.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_entries_stream_with_cursor() {
+        let mut server = mockito::Server::new_async().await;
+        let first = server.mock("GET", "/v1/entries")
+            .match_query(mockito::Matcher::Exact("limit=2&order=id&direction=asc".into()))
+            .with_status(200)
+            .with_body(r#"{"total":3,"entries":[{"id":1},{"id":2}]}"#)
+            .create_async()
+            .await;
+        let second = server.mock("GET", "/v1/entries")
+            .match_query(mockito::Matcher::UrlEncoded("after_entry_id".into(), "2".into()))
+            .with_status(200)
+            .with_body(r#"{"total":1,"entries":[{"id":3}]}"#)
+            .create_async()
+            .await;
 
+        let client = MinifluxClient::with_base_url(
+            server.host_with_port(),
+            "test_token".to_string(),
+            "http".to_string(),
+        );
+        let entries: Vec<_> = client
+            .entries_stream(EntryQuery::new().limit(2))
+            .try_collect()
+            .await
+            .unwrap();
+
+        first.assert_async().await;
+        second.assert_async().await;
+        let ids: Vec<i64> = entries.iter().map(|entry| entry.id).collect();
+        assert_eq!(ids, vec![1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_entries_stream_with_descending_cursor() {
+        let mut server = mockito::Server::new_async().await;
+        let first = server.mock("GET", "/v1/entries")
+            .match_query(mockito::Matcher::Exact("limit=2&order=id&direction=desc".into()))
+            .with_status(200)
+            .with_body(r#"{"total":3,"entries":[{"id":9},{"id":8}]}"#)
+            .create_async()
+            .await;
+        let second = server.mock("GET", "/v1/entries")
+            .match_query(mockito::Matcher::Exact("limit=2&before_entry_id=8&order=id&direction=desc".into()))
+            .with_status(200)
+            .with_body(r#"{"total":1,"entries":[{"id":5}]}"#)
+            .create_async()
+            .await;
+
+        let client = MinifluxClient::with_base_url(
+            server.host_with_port(),
+            "test_token".to_string(),
+            "http".to_string(),
+        );
+        let query = EntryQuery::new()
+            .limit(2)
+            .offset(10)
+            .order(EntryOrder::Id)
+            .direction(Direction::Desc);
+        let entries: Vec<_> = client.entries_stream(query).try_collect().await.unwrap();
+
+        first.assert_async().await;
+        second.assert_async().await;
+        let ids: Vec<i64> = entries.iter().map(|entry| entry.id).collect();
+        assert_eq!(ids, vec![9, 8, 5]);
+    }
+
+    #[tokio::test]
+    async fn test_entries_stream_with_offset() {
+        let mut server = mockito::Server::new_async().await;
+        let first = server.mock("GET", "/v1/entries")
+            .match_query(mockito::Matcher::Exact("status=unread&limit=2&order=published_at".into()))
+            .with_status(200)
+            .with_body(r#"{"total":4,"entries":[{"id":7},{"id":3}]}"#)
+            .create_async()
+            .await;
+        let second = server.mock("GET", "/v1/entries")
+            .match_query(mockito::Matcher::UrlEncoded("offset".into(), "2".into()))
+            .with_status(200)
+            .with_body(r#"{"total":4,"entries":[{"id":9},{"id":1}]}"#)
+            .create_async()
+            .await;
+
+        let client = MinifluxClient::with_base_url(
+            server.host_with_port(),
+            "test_token".to_string(),
+            "http".to_string(),
+        );
+        let query = EntryQuery::unread().limit(2).order(EntryOrder::PublishedAt);
+        let entries: Vec<_> = client.entries_stream(query).try_collect().await.unwrap();
+
+        first.assert_async().await;
+        second.assert_async().await;
+        let ids: Vec<i64> = entries.iter().map(|entry| entry.id).collect();
+        assert_eq!(ids, vec![7, 3, 9, 1]);
+    }
+
+    #[tokio::test]
+    async fn test_get_entries_page_with_filters() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server.mock("GET", "/v1/entries")
+            .match_query(mockito::Matcher::AllOf(vec![
+                mockito::Matcher::UrlEncoded("starred".into(), "true".into()),
+                mockito::Matcher::UrlEncoded("search".into(), "rust lang".into()),
+                mockito::Matcher::UrlEncoded("published_after".into(), "1704067200".into()),
+                mockito::Matcher::UrlEncoded("direction".into(), "desc".into()),
+            ]))
+            .with_status(200)
+            .with_body(r#"{"total":1,"entries":[{"id":12,"starred":true}]}"#)
+            .create_async()
+            .await;
+
+        let client = MinifluxClient::with_base_url(
+            server.host_with_port(),
+            "test_token".to_string(),
+            "http".to_string(),
+        );
+        let query = EntryQuery::new()
+            .starred(true)
+            .search("rust lang")
+            .published_after(chrono::DateTime::from_timestamp(1704067200, 0).unwrap())
+            .direction(Direction::Desc);
+        let page = client.get_entries_page(&query).await.unwrap();
+
+        mock.assert_async().await;
+        assert!(page.entries[0].starred);
+    }
+
+    #[tokio::test]
+    async fn test_list_feeds_with_mock() {
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server.mock("GET", "/v1/feeds")
+            .match_header("X-Auth-Token", "test_token")
+            .with_status(200)
+            .with_body(r#"[{"id":1,"title":"Feed","category":{"id":2,"title":"Tech"}}]"#)
+            .create_async()
+            .await;
+
+        let client = MinifluxClient::with_base_url(
+            server.host_with_port(),
+            "test_token".to_string(),
+            "http".to_string(),
+        );
+        let feeds = client.list_feeds().await.unwrap();
+
+        assert_eq!(feeds.len(), 1);
+        assert_eq!(feeds[0].category.as_ref().unwrap().id, 2);
+    }
+
+    #[tokio::test]
+    async fn test_create_feed_with_mock() {
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server.mock("POST", "/v1/feeds")
+            .match_body(mockito::Matcher::Json(serde_json::json!({
+                "feed_url": "https://example.org/feed.xml",
+                "category_id": 3,
+                "crawler": true,
+                "scraper_rules": "article"
+            })))
+            .with_status(201)
+            .with_body(r#"{"feed_id":262}"#)
+            .create_async()
+            .await;
+
+        let client = MinifluxClient::with_base_url(
+            server.host_with_port(),
+            "test_token".to_string(),
+            "http".to_string(),
+        );
+        let request = FeedCreationRequest {
+            crawler: Some(true),
+            scraper_rules: Some("article".to_string()),
+            ..FeedCreationRequest::new("https://example.org/feed.xml", 3)
+        };
+
+        assert_eq!(client.create_feed(&request).await.unwrap(), 262);
+    }
+
+    #[tokio::test]
+    async fn test_update_feed_with_mock() {
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server.mock("PUT", "/v1/feeds/262")
+            .match_body(mockito::Matcher::Json(serde_json::json!({"title": "Renamed"})))
+            .with_status(201)
+            .with_body(r#"{"id":262,"title":"Renamed"}"#)
+            .create_async()
+            .await;
+
+        let client = MinifluxClient::with_base_url(
+            server.host_with_port(),
+            "test_token".to_string(),
+            "http".to_string(),
+        );
+        let changes = FeedModificationRequest {
+            title: Some("Renamed".to_string()),
+            ..Default::default()
+        };
+
+        assert_eq!(client.update_feed(262, &changes).await.unwrap().title, "Renamed");
+    }
+
+    #[tokio::test]
+    async fn test_delete_and_refresh_feed_with_mock() {
+        let mut server = mockito::Server::new_async().await;
+        let delete = server.mock("DELETE", "/v1/feeds/262")
+            .with_status(204)
+            .create_async()
+            .await;
+        let refresh = server.mock("PUT", "/v1/feeds/263/refresh")
+            .with_status(204)
+            .create_async()
+            .await;
+
+        let client = MinifluxClient::with_base_url(
+            server.host_with_port(),
+            "test_token".to_string(),
+            "http".to_string(),
+        );
+
+        assert!(client.delete_feed(262).await.is_ok());
+        assert!(client.refresh_feed(263).await.is_ok());
+        delete.assert_async().await;
+        refresh.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_get_feed_not_found() {
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server.mock("GET", "/v1/feeds/404")
+            .with_status(404)
+            .with_body(r#"{"error_message":"Feed not found"}"#)
+            .create_async()
+            .await;
+
+        let client = MinifluxClient::with_base_url(
+            server.host_with_port(),
+            "test_token".to_string(),
+            "http".to_string(),
+        );
+
+        assert!(client.get_feed(404).await.unwrap_err().is_not_found());
+    }
+
+    #[tokio::test]
+    async fn test_feed_entries_with_mock() {
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server.mock("GET", "/v1/feeds/42/entries")
+            .match_query(mockito::Matcher::UrlEncoded("status".into(), "unread".into()))
+            .with_status(200)
+            .with_body(r#"{"total":1,"entries":[{"id":1,"feed_id":42}]}"#)
+            .create_async()
+            .await;
+
+        let client = MinifluxClient::with_base_url(
+            server.host_with_port(),
+            "test_token".to_string(),
+            "http".to_string(),
+        );
+        let page = client.feed_entries(42, &EntryQuery::unread()).await.unwrap();
+
+        assert_eq!(page.entries[0].feed_id, 42);
+    }
+
+    #[tokio::test]
+    async fn test_create_and_rename_category_with_mock() {
+        let mut server = mockito::Server::new_async().await;
+        let create = server.mock("POST", "/v1/categories")
+            .match_body(mockito::Matcher::Json(serde_json::json!({"title": "Rust"})))
+            .with_status(201)
+            .with_body(r#"{"id":5,"title":"Rust","user_id":1}"#)
+            .create_async()
+            .await;
+        let rename = server.mock("PUT", "/v1/categories/5")
+            .match_body(mockito::Matcher::Json(serde_json::json!({"title": "Rustlang"})))
+            .with_status(201)
+            .with_body(r#"{"id":5,"title":"Rustlang","user_id":1}"#)
+            .create_async()
+            .await;
+
+        let client = MinifluxClient::with_base_url(
+            server.host_with_port(),
+            "test_token".to_string(),
+            "http".to_string(),
+        );
+
+        assert_eq!(client.create_category("Rust").await.unwrap().id, 5);
+        assert_eq!(client.update_category(5, "Rustlang").await.unwrap().title, "Rustlang");
+        create.assert_async().await;
+        rename.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_category_feeds_with_mock() {
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server.mock("GET", "/v1/categories/5/feeds")
+            .with_status(200)
+            .with_body(r#"[{"id":1,"title":"A"},{"id":2,"title":"B"}]"#)
+            .create_async()
+            .await;
+
+        let client = MinifluxClient::with_base_url(
+            server.host_with_port(),
+            "test_token".to_string(),
+            "http".to_string(),
+        );
+
+        assert_eq!(client.get_category_feeds(5).await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_mark_all_as_read_with_mock() {
+        let mut server = mockito::Server::new_async().await;
+        let category = server.mock("PUT", "/v1/categories/5/mark-all-as-read")
+            .with_status(204)
+            .create_async()
+            .await;
+        let feed = server.mock("PUT", "/v1/feeds/7/mark-all-as-read")
+            .with_status(204)
+            .create_async()
+            .await;
+        let user = server.mock("PUT", "/v1/users/1/mark-all-as-read")
+            .with_status(204)
+            .create_async()
+            .await;
+
+        let client = MinifluxClient::with_base_url(
+            server.host_with_port(),
+            "test_token".to_string(),
+            "http".to_string(),
+        );
+
+        assert!(client.mark_category_as_read(5).await.is_ok());
+        assert!(client.mark_feed_as_read(7).await.is_ok());
+        assert!(client.mark_user_as_read(1).await.is_ok());
+        category.assert_async().await;
+        feed.assert_async().await;
+        user.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_delete_category_with_mock() {
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server.mock("DELETE", "/v1/categories/5")
+            .with_status(400)
+            .with_body(r#"{"error_message":"This category cannot be removed"}"#)
+            .create_async()
+            .await;
+
+        let client = MinifluxClient::with_base_url(
+            server.host_with_port(),
+            "test_token".to_string(),
+            "http".to_string(),
+        );
+        let error = client.delete_category(5).await.unwrap_err();
+
+        assert!(error.to_string().contains("This category cannot be removed"));
+    }
+
+    #[tokio::test]
+    async fn test_export_and_import_opml_with_mock() {
+        let mut server = mockito::Server::new_async().await;
+        let document = r#"<?xml version="1.0" encoding="UTF-8"?><opml version="2.0"><body></body></opml>"#;
+        let export = server.mock("GET", "/v1/export")
+            .with_status(200)
+            .with_header("content-type", "text/xml")
+            .with_body(document)
+            .create_async()
+            .await;
+        let import = server.mock("POST", "/v1/import")
+            .match_body(document)
+            .with_status(201)
+            .with_body(r#"{"message":"Feeds imported successfully"}"#)
+            .create_async()
+            .await;
+
+        let client = MinifluxClient::with_base_url(
+            server.host_with_port(),
+            "test_token".to_string(),
+            "http".to_string(),
+        );
+        let exported = client.export_opml().await.unwrap();
+        assert_eq!(exported, document);
+        assert!(client.import_opml(exported.into_bytes()).await.is_ok());
+        export.assert_async().await;
+        import.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_apply_opml_with_mock() {
+        let mut server = mockito::Server::new_async().await;
+        let _feeds = server.mock("GET", "/v1/feeds")
+            .with_status(200)
+            .with_body(r#"[
+                {"id":1,"feed_url":"https://keep.example","category":{"id":10,"title":"Tech"}},
+                {"id":2,"feed_url":"https://drop.example","category":{"id":10,"title":"Tech"}}
+            ]"#)
+            .create_async()
+            .await;
+        let _categories = server.mock("GET", "/v1/categories")
+            .with_status(200)
+            .with_body(r#"[{"id":10,"title":"Tech"}]"#)
+            .create_async()
+            .await;
+        let create_category = server.mock("POST", "/v1/categories")
+            .match_body(mockito::Matcher::Json(serde_json::json!({"title": "News"})))
+            .with_status(201)
+            .with_body(r#"{"id":11,"title":"News"}"#)
+            .create_async()
+            .await;
+        let create_feed = server.mock("POST", "/v1/feeds")
+            .match_body(mockito::Matcher::Json(serde_json::json!({
+                "feed_url": "https://new.example",
+                "category_id": 11
+            })))
+            .with_status(201)
+            .with_body(r#"{"feed_id":3}"#)
+            .create_async()
+            .await;
+        let delete_feed = server.mock("DELETE", "/v1/feeds/2")
+            .with_status(204)
+            .create_async()
+            .await;
+
+        let client = MinifluxClient::with_base_url(
+            server.host_with_port(),
+            "test_token".to_string(),
+            "http".to_string(),
+        );
+        let desired = Opml::parse(r#"<opml version="2.0"><body>
+            <outline text="Tech"><outline text="Keep" xmlUrl="https://keep.example"/></outline>
+            <outline text="News"><outline text="New" xmlUrl="https://new.example"/></outline>
+        </body></opml>"#).unwrap();
+        let diff = client.apply_opml(&desired, true).await.unwrap();
+
+        assert_eq!(diff.added.len(), 1);
+        assert_eq!(diff.removed.len(), 1);
+        assert!(diff.moved.is_empty());
+        create_category.assert_async().await;
+        create_feed.assert_async().await;
+        delete_feed.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_discover_with_mock() {
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server.mock("POST", "/v1/discover")
+            .match_body(mockito::Matcher::Json(serde_json::json!({"url": "https://example.org"})))
+            .with_status(200)
+            .with_body(r#"[{"url":"https://example.org/feed.xml","title":"Example","type":"rss"}]"#)
+            .create_async()
+            .await;
+
+        let client = MinifluxClient::with_base_url(
+            server.host_with_port(),
+            "test_token".to_string(),
+            "http".to_string(),
+        );
+        let feeds = client.discover("https://example.org").await.unwrap();
+
+        assert_eq!(feeds.len(), 1);
+        assert_eq!(feeds[0].kind, "rss");
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_with_mock() {
+        let mut server = mockito::Server::new_async().await;
+        let _discover = server.mock("POST", "/v1/discover")
+            .with_status(200)
+            .with_body(r#"[
+                {"url":"https://example.org/rss.xml","title":"RSS","type":"rss"},
+                {"url":"https://example.org/atom.xml","title":"Atom","type":"atom"}
+            ]"#)
+            .create_async()
+            .await;
+        let _categories = server.mock("GET", "/v1/categories")
+            .with_status(200)
+            .with_body(r#"[{"id":4,"title":"Tech"}]"#)
+            .create_async()
+            .await;
+        let create = server.mock("POST", "/v1/feeds")
+            .match_body(mockito::Matcher::Json(serde_json::json!({
+                "feed_url": "https://example.org/atom.xml",
+                "category_id": 4
+            })))
+            .with_status(201)
+            .with_body(r#"{"feed_id":99}"#)
+            .create_async()
+            .await;
+
+        let client = MinifluxClient::with_base_url(
+            server.host_with_port(),
+            "test_token".to_string(),
+            "http".to_string(),
+        );
+
+        assert_eq!(client.subscribe("https://example.org", "tech").await.unwrap(), 99);
+        create.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_without_candidates() {
+        let mut server = mockito::Server::new_async().await;
+        let _discover = server.mock("POST", "/v1/discover")
+            .with_status(200)
+            .with_body("[]")
+            .create_async()
+            .await;
+
+        let client = MinifluxClient::with_base_url(
+            server.host_with_port(),
+            "test_token".to_string(),
+            "http".to_string(),
+        );
+
+        let error = client.subscribe("https://example.org", "Tech").await.unwrap_err();
+        assert!(matches!(error, MinifluxError::NoFeedFound(_)));
+        assert!(error.status().is_none());
+        assert_eq!(error.to_string(), "No feed found at https://example.org");
+    }
+
+    #[tokio::test]
+    async fn test_set_status_with_mock() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server.mock("PUT", "/v1/entries")
+            .match_body(mockito::Matcher::Json(serde_json::json!({
+                "entry_ids": [1, 2],
+                "status": "unread"
+            })))
+            .with_status(204)
+            .create_async()
+            .await;
+
+        let client = MinifluxClient::with_base_url(
+            server.host_with_port(),
+            "test_token".to_string(),
+            "http".to_string(),
+        );
+
+        assert!(client.set_status(vec![1, 2], EntryStatus::Unread).await.is_ok());
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_toggle_bookmark_and_save_with_mock() {
+        let mut server = mockito::Server::new_async().await;
+        let bookmark = server.mock("PUT", "/v1/entries/5/bookmark")
+            .with_status(204)
+            .create_async()
+            .await;
+        let save = server.mock("POST", "/v1/entries/5/save")
+            .with_status(202)
+            .create_async()
+            .await;
+
+        let client = MinifluxClient::with_base_url(
+            server.host_with_port(),
+            "test_token".to_string(),
+            "http".to_string(),
+        );
+
+        assert!(client.toggle_bookmark(5).await.is_ok());
+        assert!(client.save_entry(5).await.is_ok());
+        bookmark.assert_async().await;
+        save.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_update_entry_with_mock() {
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server.mock("PUT", "/v1/entries/5")
+            .match_body(mockito::Matcher::Json(serde_json::json!({"title": "New title"})))
+            .with_status(201)
+            .with_body(r#"{"id":5,"title":"New title","content":"old"}"#)
+            .create_async()
+            .await;
+
+        let client = MinifluxClient::with_base_url(
+            server.host_with_port(),
+            "test_token".to_string(),
+            "http".to_string(),
+        );
+        let entry = client.update_entry(5, Some("New title"), None).await.unwrap();
+
+        assert_eq!(entry.title, "New title");
+    }
+
+    #[tokio::test]
+    async fn test_get_and_update_enclosure_with_mock() {
+        let mut server = mockito::Server::new_async().await;
+        let get = server.mock("GET", "/v1/enclosures/9")
+            .with_status(200)
+            .with_body(r#"{"id":9,"entry_id":5,"url":"http://example.org/a.mp3","mime_type":"audio/mpeg","size":1024,"media_progression":0}"#)
+            .create_async()
+            .await;
+        let update = server.mock("PUT", "/v1/enclosures/9")
+            .match_body(mockito::Matcher::Json(serde_json::json!({"media_progression": 120})))
+            .with_status(204)
+            .create_async()
+            .await;
+
+        let client = MinifluxClient::with_base_url(
+            server.host_with_port(),
+            "test_token".to_string(),
+            "http".to_string(),
+        );
+
+        let enclosure = client.get_enclosure(9).await.unwrap();
+        assert_eq!(enclosure.entry_id, 5);
+        assert!(enclosure.is_audio());
+        assert!(client.update_enclosure(9, 120).await.is_ok());
+        get.assert_async().await;
+        update.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_icons_with_mock() {
+        let mut server = mockito::Server::new_async().await;
+        let feed_icon = server.mock("GET", "/v1/feeds/42/icon")
+            .with_status(200)
+            .with_body(r#"{"id":11,"data":"image/png;base64,iVBORw0KGgo=","mime_type":"image/png"}"#)
+            .create_async()
+            .await;
+        // Second lookup must come from the cache
+        let icon = server.mock("GET", "/v1/icons/11")
+            .with_status(200)
+            .with_body(r#"{"id":11,"data":"image/png;base64,iVBORw0KGgo=","mime_type":"image/png"}"#)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let client = MinifluxClient::with_base_url(
+            server.host_with_port(),
+            "test_token".to_string(),
+            "http".to_string(),
+        );
+        let dir = std::env::temp_dir().join(format!("miniflux-client-icons-{}", std::process::id()));
+        let cache = crate::models::IconCache::new(&dir);
+
+        assert_eq!(client.get_feed_icon(42).await.unwrap().id, 11);
+        assert_eq!(client.cached_icon(&cache, 11).await.unwrap().mime_type, "image/png");
+        assert_eq!(client.cached_icon(&cache, 11).await.unwrap().mime_type, "image/png");
+        feed_icon.assert_async().await;
+        icon.assert_async().await;
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_healthcheck_and_version_with_mock() {
+        let mut server = mockito::Server::new_async().await;
+        let _health = server.mock("GET", "/healthcheck")
+            .with_status(200)
+            .with_body("OK")
+            .create_async()
+            .await;
+        let _version = server.mock("GET", "/v1/version")
+            .with_status(200)
+            .with_body(r#"{"version":"2.2.1","commit":"abc","build_date":"2024-07-01","go_version":"go1.22","compiler":"gc","arch":"amd64","os":"linux"}"#)
+            .create_async()
+            .await;
+
+        let client = MinifluxClient::with_base_url(
+            server.host_with_port(),
+            "test_token".to_string(),
+            "http".to_string(),
+        );
+
+        assert!(client.healthcheck().await.is_ok());
+        assert_eq!(client.version().await.unwrap().version, "2.2.1");
+    }
+
+    #[tokio::test]
+    async fn test_healthcheck_down() {
+        let mut server = mockito::Server::new_async().await;
+        let health = server.mock("GET", "/healthcheck")
+            .with_status(500)
+            .with_body("Database error")
+            .expect(1)
+            .create_async()
+            .await;
+
+        let client = MinifluxClient::with_base_url(
+            server.host_with_port(),
+            "test_token".to_string(),
+            "http".to_string(),
+        )
+        .with_retry(RetryPolicy::none());
+
+        assert!(client.healthcheck().await.unwrap_err().is_server_error());
+        health.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_healthcheck_unhealthy() {
+        let mut server = mockito::Server::new_async().await;
+        let _health = server.mock("GET", "/healthcheck")
+            .with_status(200)
+            .with_body("Database error")
+            .create_async()
+            .await;
+
+        let client = MinifluxClient::with_base_url(
+            server.host_with_port(),
+            "test_token".to_string(),
+            "http".to_string(),
+        );
+
+        let error = client.healthcheck().await.unwrap_err();
+        assert!(matches!(error, MinifluxError::Unhealthy(ref body) if body == "Database error"));
+        assert!(error.status().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_me_unauthorized() {
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server.mock("GET", "/v1/me")
+            .with_status(401)
+            .with_body(r#"{"error_message":"Access Unauthorized"}"#)
+            .create_async()
+            .await;
+
+        let client = MinifluxClient::with_base_url(
+            server.host_with_port(),
+            "bad_token".to_string(),
+            "http".to_string(),
+        );
+
+        let error = client.me().await.unwrap_err();
+        assert!(error.is_unauthorized());
+        assert!(error.to_string().contains("Access Unauthorized"));
+    }
+
+    #[tokio::test]
+    async fn test_users_with_mock() {
+        let mut server = mockito::Server::new_async().await;
+        let me = server.mock("GET", "/v1/me")
+            .with_status(200)
+            .with_body(r#"{"id":1,"username":"admin","is_admin":true}"#)
+            .create_async()
+            .await;
+        let create = server.mock("POST", "/v1/users")
+            .match_body(mockito::Matcher::Json(serde_json::json!({"username": "bob", "password": "secret", "is_admin": false})))
+            .with_status(201)
+            .with_body(r#"{"id":2,"username":"bob"}"#)
+            .create_async()
+            .await;
+        let by_name = server.mock("GET", "/v1/users/bob")
+            .with_status(200)
+            .with_body(r#"{"id":2,"username":"bob"}"#)
+            .create_async()
+            .await;
+        let update = server.mock("PUT", "/v1/users/2")
+            .match_body(mockito::Matcher::Json(serde_json::json!({"is_admin": true})))
+            .with_status(201)
+            .with_body(r#"{"id":2,"username":"bob","is_admin":true}"#)
+            .create_async()
+            .await;
+        let delete = server.mock("DELETE", "/v1/users/2")
+            .with_status(204)
+            .create_async()
+            .await;
+
+        let client = MinifluxClient::with_base_url(
+            server.host_with_port(),
+            "test_token".to_string(),
+            "http".to_string(),
+        );
+
+        assert!(client.me().await.unwrap().is_admin);
+        let user = client.create_user(&UserCreationRequest::new("bob", "secret")).await.unwrap();
+        assert_eq!(client.get_user_by_username("bob").await.unwrap().id, user.id);
+        let changes = UserModificationRequest {
+            is_admin: Some(true),
+            ..Default::default()
+        };
+        assert!(client.update_user(user.id, &changes).await.unwrap().is_admin);
+        assert!(client.delete_user(user.id).await.is_ok());
+        for mock in [me, create, by_name, update, delete] {
+            mock.assert_async().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_feed_counters_with_mock() {
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server.mock("GET", "/v1/feeds/counters")
+            .with_status(200)
+            .with_body(r#"{"reads":{"1":10},"unreads":{"1":3,"2":4}}"#)
+            .create_async()
+            .await;
+
+        let client = MinifluxClient::with_base_url(
+            server.host_with_port(),
+            "test_token".to_string(),
+            "http".to_string(),
+        );
+
+        let counters = client.feed_counters().await.unwrap();
+        assert_eq!(counters.total_unread(), 7);
+        assert_eq!(counters.reads[&1], 10);
+    }
+
+    #[tokio::test]
+    async fn test_full_base_url_with_basic_auth() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server.mock("GET", "/miniflux/v1/me")
+            // admin:secret
+            .match_header("authorization", "Basic YWRtaW46c2VjcmV0")
+            .match_header("X-Auth-Token", mockito::Matcher::Missing)
+            .with_status(200)
+            .with_body(r#"{"id":1,"username":"admin"}"#)
+            .create_async()
+            .await;
+
+        let client = MinifluxClient::with_auth(
+            format!("{}/miniflux/", server.url()),
+            Auth::basic("admin", "secret"),
+        );
+
+        assert_eq!(client.me().await.unwrap().username, "admin");
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_host_with_path_prefix() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server.mock("GET", "/reader/healthcheck")
+            .with_status(200)
+            .with_body("OK")
+            .create_async()
+            .await;
+
+        let client = MinifluxClient::with_base_url(
+            format!("{}/reader", server.host_with_port()),
+            "test_token".to_string(),
+            "http".to_string(),
+        );
+
+        assert!(client.healthcheck().await.is_ok());
+        mock.assert_async().await;
+    }
+
+    #[test]
+    fn test_miniflux_client_basic_auth_serde() {
+        let client = MinifluxClient::with_auth("https://example.com/miniflux".to_string(), Auth::basic("admin", "secret"));
+        let serialized = serde_json::to_string(&client).unwrap();
+        let deserialized: MinifluxClient = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(deserialized.url, "https://example.com/miniflux");
+        assert_eq!(deserialized.auth, Auth::basic("admin", "secret"));
+    }
+
+    #[tokio::test]
+    async fn test_retries_bad_gateway() {
+        let mut server = mockito::Server::new_async().await;
+        let failing = server.mock("GET", "/v1/categories")
+            .with_status(502)
+            .expect(1)
+            .create_async()
+            .await;
+        let working = server.mock("GET", "/v1/categories")
+            .with_status(200)
+            .with_body(r#"[{"id":1,"title":"News"}]"#)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let client = MinifluxClient::with_base_url(
+            server.host_with_port(),
+            "test_token".to_string(),
+            "http".to_string(),
+        )
+        .with_retry(RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(5),
+            jitter: 0.0,
+        });
+
+        let categories = client.get_categories().await.unwrap();
+        assert_eq!(categories[0].title, "News");
+        failing.assert_async().await;
+        working.assert_async().await;
+    }
+}
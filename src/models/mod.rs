@@ -1,10 +1,48 @@
+mod auth;
+mod backend;
+mod category;
+mod content;
+mod delivery;
+mod digest;
+mod entry;
+mod error;
+mod feed;
+mod http;
+mod icon;
 mod matrix;
 mod miniflux;
 mod telegram;
 mod model;
+mod normalize;
+mod notifier;
+mod opml;
+mod query;
+mod retry;
+mod summary;
+mod user;
+mod version;
 
+pub use auth::Auth;
+pub use backend::{backend, AnthropicBackend, ChatBackend, ChatRequest, OllamaBackend, OpenAiBackend};
+pub use category::Category;
+pub use content::ContentFetcher;
+pub use delivery::{AckPolicy, Acknowledger, DeliveryTracker};
+pub use digest::{Digest, DigestItem};
+pub use entry::{Enclosure, EntriesResponse, Entry, EntryStatus};
+pub use error::MinifluxError;
+pub use feed::{DiscoveredFeed, Feed, FeedCounters, FeedCreationRequest, FeedIcon, FeedModificationRequest, Icon};
+pub use http::HttpConfig;
+pub use icon::IconCache;
 pub use telegram::TelegramClient;
 pub use matrix::MatrixClient;
 pub use miniflux::MinifluxClient;
 pub use model::Model;
+pub use normalize::{estimate_tokens, truncate, ContentFormat, Normalizer, CHARS_PER_TOKEN};
+pub use notifier::{Capabilities, Markup, Notification, Notifier};
+pub use opml::{Opml, OpmlDiff, OpmlError, OpmlFeed};
+pub use query::{Direction, EntryOrder, EntryQuery};
+pub use retry::{RetryPolicy, RetryRequest};
+pub use summary::SummaryCache;
+pub use user::{User, UserCreationRequest, UserModificationRequest};
+pub use version::VersionInfo;
 pub type CustomError = Box<dyn std::error::Error>;
@@ -1,131 +1,162 @@
 use serde::{Serialize, Deserialize};
 use reqwest::Client;
 use serde_json::{Value, json};
-use tracing::debug;
+use std::sync::Arc;
+use tracing::warn;
+
+use super::{estimate_tokens, AnthropicBackend, ChatBackend, ChatRequest, CustomError, Digest, OpenAiBackend, RetryPolicy};
+
+const MAX_TOKENS: u32 = 4096;
+
+fn default_max_tokens() -> u32 {
+    MAX_TOKENS
+}
+
+fn default_backend() -> Arc<dyn ChatBackend> {
+    Arc::new(OpenAiBackend)
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Model {
+    // API spoken by the model server, OpenAI compatible by default
+    #[serde(skip, default = "default_backend")]
+    backend: Arc<dyn ChatBackend>,
     url: String,
     api_key: String,
     model: String,
     model_description: String,
     prompt: String,
+    // Mandatory for Anthropic, ignored by the other backends
+    #[serde(default = "default_max_tokens")]
+    pub max_tokens: u32,
+    // Asks OpenAI for a strict JSON schema answer, many compatible servers
+    // reject `response_format` so it is opt-in
+    #[serde(default)]
+    pub json_schema: bool,
+    #[serde(skip)]
+    client: Client,
+    #[serde(skip)]
+    retry: RetryPolicy,
 }
 
 impl Model {
     pub fn new(url: String, api_key: String, model: String,
             model_description: String, prompt: String) -> Self {
         Model {
+            backend: default_backend(),
             url,
             api_key,
             model,
             model_description,
             prompt,
+            max_tokens: MAX_TOKENS,
+            json_schema: false,
+            client: Client::new(),
+            retry: RetryPolicy::default(),
         }
     }
 
-    pub async fn process_news(&self, news: &Vec<Value>) -> Result<String, Box<dyn std::error::Error>> {
-        debug!("Processing news...");
-        let url = format!("{}/v1/chat/completions", self.url);
-        debug!("URL: {}", url);
-        let content = json!({
-            "model": self.model,
-            "messages": [
-                {
-                    "role": "assistant",
-                    "content": &self.model_description
-                },
-                {
-                    "role": "user",
-                    "content": format!("{}. Noticias: {}",
-                        &self.prompt,
-                        serde_json::to_string(news)?)
-                }
-            ]
-        });
-        let client = Client::new();
-        let response = client
-            .post(&url)
-            .header("content-type", "application/json")
-            .header("Authorization", format!("Bearer {}",&self.api_key))
-            .json(&content)
-            .send()
-            .await?;
-        let response = response.json::<serde_json::Value>().await?;
-        println!("Response: {:?}", response);
-        //{"content": Array [Object {"text": String("
-        let content = response
-            .get("choices")
-            .ok_or("No content")?
-            .as_array()
-            .ok_or("No array")?
-            .first()
-            .ok_or("No first element")?
-            .get("message")
-            .ok_or("No message")?
-            .get("content")
-            .ok_or("No content")?
-            .as_str()
-            .ok_or("No string")?;
-        println!("Response: {:?}", content);
-        debug!("Response: {:?}", content);
-        Ok(content.to_string())
+    pub fn with_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
     }
 
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = max_tokens;
+        self
+    }
+
+    pub fn with_json_schema(mut self, json_schema: bool) -> Self {
+        self.json_schema = json_schema;
+        self
+    }
+
+    pub fn with_backend(mut self, backend: Box<dyn ChatBackend>) -> Self {
+        self.backend = backend.into();
+        self
+    }
+
+    pub fn backend(&self) -> &dyn ChatBackend {
+        self.backend.as_ref()
+    }
+
+    // Changes whenever the answers would, used to key cached summaries
+    pub fn fingerprint(&self) -> String {
+        format!("{}\n{}\n{}\n{}", self.backend.name(), self.model, self.model_description, self.prompt)
+    }
 
+    // Tokens the prompt and description add to every call
+    pub fn overhead_tokens(&self) -> usize {
+        estimate_tokens(&self.prompt) + estimate_tokens(&self.model_description) + estimate_tokens(". Noticias: ")
+    }
+
+    // Summarizes the news with the configured backend, asking once more with
+    // the validation error when the answer is not a digest
+    pub async fn summarize(&self, news: &[Value]) -> Result<Digest, CustomError> {
+        let mut messages = vec![self.user_message(&serde_json::to_string(news)?)];
+        let answer = self.chat(&messages).await?;
+        let error = match Digest::parse(&answer) {
+            Ok(digest) => return Ok(digest),
+            Err(error) => error,
+        };
+        warn!("Invalid answer from the model, asking again: {}", error);
+        messages.push(json!({"role": "assistant", "content": answer}));
+        messages.push(json!({
+            "role": "user",
+            "content": format!("{}. Answer again with only the JSON document, without code fences.", error)
+        }));
+        Ok(Digest::parse(&self.chat(&messages).await?)?)
+    }
+
+    async fn chat(&self, messages: &[Value]) -> Result<String, CustomError> {
+        self.backend.chat(&self.request(messages)).await
+    }
+
+    fn request<'a>(&'a self, messages: &'a [Value]) -> ChatRequest<'a> {
+        ChatRequest {
+            client: &self.client,
+            retry: &self.retry,
+            url: &self.url,
+            api_key: &self.api_key,
+            model: &self.model,
+            system: &self.model_description,
+            messages,
+            max_tokens: self.max_tokens,
+            json_schema: self.json_schema,
+        }
+    }
+
+    fn user_message(&self, news: &str) -> Value {
+        json!({
+            "role": "user",
+            "content": format!("{}. Noticias: {}", &self.prompt, news)
+        })
+    }
+
+    // OpenAI chat completions, whatever the configured backend
+    pub async fn process_news(&self, news: &Vec<Value>) -> Result<String, Box<dyn std::error::Error>> {
+        let messages = [self.user_message(&serde_json::to_string(news)?)];
+        OpenAiBackend.chat(&self.request(&messages)).await
+    }
+
+    // Anthropic Messages API, whatever the configured backend
     pub async fn process_news_from_feed(&self, news: &str) -> Result<String, Box<dyn std::error::Error>> {
-        debug!("Processing news...");
-        let url = format!("{}/v1/messages", self.url);
-        debug!("URL: {}", url);
-        let content = json!({
-            "model": self.model,
-            "messages": [
-                {
-                    "role": "assistant",
-                    "content": &self.model_description
-                },
-                {
-                    "role": "user",
-                    "content": format!("{}. Noticias: {}",
-                        &self.prompt,
-                        serde_json::to_string(news)?)
-                }
-            ]
-        });
-        let client = Client::new();
-        let response = client
-            .post(&url)
-            .header("content-type", "application/json")
-            .header("Authorization", format!("Bearer {}",&self.api_key))
-            .json(&content)
-            .send()
-            .await?;
-        let response = response.json::<serde_json::Value>().await?;
-        println!("Response: {:?}", response);
-        //{"content": Array [Object {"text": String("
-        let content = response
-            .get("content")
-            .ok_or("No content")?
-            .as_array()
-            .ok_or("No array")?
-            .first()
-            .ok_or("No first element")?
-            .get("message")
-            .ok_or("No message")?
-            .get("content")
-            .ok_or("No content")?
-            .as_str()
-            .ok_or("No string")?;
-        println!("Response: {:?}", content);
-        debug!("Response: {:?}", content);
-        Ok("".to_string())
+        let messages = [self.user_message(news)];
+        AnthropicBackend.chat(&self.request(&messages)).await
     }
 }
 
 #[cfg(test)]
 mod model_test {
-    use super::Model;
+    use super::{Digest, Model, RetryPolicy};
+    use crate::models::{AnthropicBackend, ChatBackend, ChatRequest, OllamaBackend};
     use dotenv::dotenv;
+    use std::time::Duration;
     use tracing::debug;
 
     #[test]
@@ -642,5 +673,348 @@ mod model_test {
         debug!("Entries: {:?}", entries);
         assert!(entries.is_ok());
     }
-}
 
+    #[tokio::test]
+    async fn test_process_news_retries_after_overload() {
+        let mut server = mockito::Server::new_async().await;
+        let overloaded = server.mock("POST", "/v1/chat/completions")
+            .with_status(503)
+            .with_header("retry-after", "0")
+            .expect(1)
+            .create_async()
+            .await;
+        let answered = server.mock("POST", "/v1/chat/completions")
+            .with_status(200)
+            .with_body(r#"{"choices":[{"message":{"role":"assistant","content":"{\"news\":[]}"}}]}"#)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let model = Model::new(
+            server.url(),
+            "key".to_string(),
+            "gpt-4".to_string(),
+            "desc".to_string(),
+            "prompt".to_string(),
+        )
+        .with_retry(RetryPolicy {
+            max_attempts: 2,
+            base_delay: Duration::from_secs(60),
+            max_delay: Duration::from_secs(60),
+            jitter: 0.0,
+        });
+
+        // The Retry-After header wins over the one minute backoff
+        let content = model.process_news(&vec![serde_json::json!({"title": "t"})]).await.unwrap();
+        assert_eq!(content, r#"{"news":[]}"#);
+        overloaded.assert_async().await;
+        answered.assert_async().await;
+    }
+
+    #[test]
+    fn test_default_backend() {
+        let json = r#"{"url":"u","api_key":"k","model":"m","model_description":"d","prompt":"p"}"#;
+        let model: Model = serde_json::from_str(json).unwrap();
+        assert_eq!(model.backend().name(), "openai");
+    }
+
+    #[tokio::test]
+    async fn test_summarize_with_ollama() {
+        let mut server = mockito::Server::new_async().await;
+        let chat = server.mock("POST", "/api/chat")
+            .match_header("authorization", mockito::Matcher::Missing)
+            .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+                "model": "llama3",
+                "stream": false,
+                "messages": [{"role": "system", "content": "desc"}]
+            })))
+            .with_status(200)
+            .with_body(r#"{"model":"llama3","message":{"role":"assistant","content":"{\"news\":[]}"},"done":true}"#)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let model = Model::new(
+            server.url(),
+            String::new(),
+            "llama3".to_string(),
+            "desc".to_string(),
+            "prompt".to_string(),
+        )
+        .with_backend(Box::new(OllamaBackend));
+
+        let digest = model.summarize(&[serde_json::json!({"title": "t"})]).await.unwrap();
+        assert_eq!(digest, Digest::default());
+        chat.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_summarize_with_ollama_error() {
+        let mut server = mockito::Server::new_async().await;
+        let _chat = server.mock("POST", "/api/chat")
+            .with_status(404)
+            .with_body(r#"{"error":"model 'llama3' not found"}"#)
+            .create_async()
+            .await;
+
+        let model = Model::new(
+            server.url(),
+            String::new(),
+            "llama3".to_string(),
+            "desc".to_string(),
+            "prompt".to_string(),
+        )
+        .with_backend(Box::new(OllamaBackend))
+        .with_retry(RetryPolicy::none());
+
+        let error = model.summarize(&[serde_json::json!({"title": "t"})]).await.unwrap_err();
+        assert!(error.to_string().contains("not found"));
+    }
+
+    fn anthropic(url: String) -> Model {
+        Model::new(
+            url,
+            "key".to_string(),
+            "claude-sonnet".to_string(),
+            "desc".to_string(),
+            "prompt".to_string(),
+        )
+        .with_backend(Box::new(AnthropicBackend))
+        .with_max_tokens(1024)
+        .with_retry(RetryPolicy::none())
+    }
+
+    #[tokio::test]
+    async fn test_process_news_from_feed() {
+        let mut server = mockito::Server::new_async().await;
+        let messages = server.mock("POST", "/v1/messages")
+            .match_header("x-api-key", "key")
+            .match_header("anthropic-version", "2023-06-01")
+            .match_header("authorization", mockito::Matcher::Missing)
+            .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+                "model": "claude-sonnet",
+                "max_tokens": 1024,
+                "system": "desc",
+                "messages": [{"role": "user", "content": "prompt. Noticias: [{\"title\":\"t\"}]"}]
+            })))
+            .with_status(200)
+            .with_body(r#"{"id":"msg_1","type":"message","role":"assistant","content":[{"type":"text","text":"{\"news\":"},{"type":"text","text":"[]}"}],"stop_reason":"end_turn"}"#)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let model = anthropic(server.url());
+        let digest = model.summarize(&[serde_json::json!({"title": "t"})]).await.unwrap();
+        assert_eq!(digest, Digest::default());
+        messages.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_process_news_from_feed_truncated() {
+        let mut server = mockito::Server::new_async().await;
+        let _messages = server.mock("POST", "/v1/messages")
+            .with_status(200)
+            .with_body(r#"{"content":[{"type":"text","text":"{\"news\":[{\"title"}],"stop_reason":"max_tokens"}"#)
+            .create_async()
+            .await;
+
+        let error = anthropic(server.url()).process_news_from_feed("[]").await.unwrap_err();
+        assert!(error.to_string().contains("truncated at 1024 tokens"));
+    }
+
+    #[tokio::test]
+    async fn test_process_news_from_feed_error() {
+        let mut server = mockito::Server::new_async().await;
+        let _messages = server.mock("POST", "/v1/messages")
+            .with_status(401)
+            .with_body(r#"{"type":"error","error":{"type":"authentication_error","message":"invalid x-api-key"}}"#)
+            .create_async()
+            .await;
+
+        let error = anthropic(server.url()).process_news_from_feed("[]").await.unwrap_err();
+        assert!(error.to_string().contains("invalid x-api-key"));
+    }
+
+    #[tokio::test]
+    async fn test_summarize_requests_json_schema() {
+        let mut server = mockito::Server::new_async().await;
+        let completions = server.mock("POST", "/v1/chat/completions")
+            .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+                "messages": [{"role": "system", "content": "desc"}],
+                "response_format": {"type": "json_schema", "json_schema": {"name": "digest", "strict": true}}
+            })))
+            .with_status(200)
+            .with_body(r#"{"choices":[{"message":{"role":"assistant","content":"```json\n{\"news\":[{\"title\":\"One\",\"url\":\"https://example.org/1\",\"summary\":\"First\"}]}\n```"}}]}"#)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let model = Model::new(
+            format!("{}/", server.url()),
+            "key".to_string(),
+            "gpt-4".to_string(),
+            "desc".to_string(),
+            "prompt".to_string(),
+        )
+        .with_json_schema(true);
+        let digest = model.summarize(&[serde_json::json!({"title": "t"})]).await.unwrap();
+        assert_eq!(digest.news.len(), 1);
+        assert_eq!(digest.news[0].url, "https://example.org/1");
+        completions.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_summarize_reprompts_once_on_invalid_answer() {
+        let mut server = mockito::Server::new_async().await;
+        let invalid = server.mock("POST", "/v1/messages")
+            .with_status(200)
+            .with_body(r#"{"content":[{"type":"text","text":"{\"news\":[{\"title\":\"One\",\"url\":\"https://example.org/1\"}]}"}],"stop_reason":"end_turn"}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let fixed = server.mock("POST", "/v1/messages")
+            .match_body(mockito::Matcher::Regex("missing field `summary`".to_string()))
+            .with_status(200)
+            .with_body(r#"{"content":[{"type":"text","text":"{\"news\":[{\"title\":\"One\",\"url\":\"https://example.org/1\",\"summary\":\"First\"}]}"}],"stop_reason":"end_turn"}"#)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let digest = anthropic(server.url()).summarize(&[serde_json::json!({"title": "t"})]).await.unwrap();
+        assert_eq!(digest.news[0].summary, "First");
+        invalid.assert_async().await;
+        fixed.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_summarize_gives_up_after_reprompt() {
+        let mut server = mockito::Server::new_async().await;
+        let answers = server.mock("POST", "/api/chat")
+            .with_status(200)
+            .with_body(r#"{"message":{"role":"assistant","content":"Sorry, no news today"},"done":true}"#)
+            .expect(2)
+            .create_async()
+            .await;
+
+        let model = Model::new(
+            server.url(),
+            String::new(),
+            "llama3".to_string(),
+            "desc".to_string(),
+            "prompt".to_string(),
+        )
+        .with_backend(Box::new(OllamaBackend));
+        let error = model.summarize(&[serde_json::json!({"title": "t"})]).await.unwrap_err();
+        assert!(error.to_string().contains("not a valid digest"));
+        answers.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_openai_without_json_schema() {
+        let mut server = mockito::Server::new_async().await;
+        let completions = server.mock("POST", "/v1/chat/completions")
+            .match_request(|request| !request.utf8_lossy_body().unwrap().contains("response_format"))
+            .with_status(200)
+            .with_body(r#"{"choices":[{"message":{"role":"assistant","content":"{\"news\":[]}"}}]}"#)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let model = Model::new(
+            server.url(),
+            "key".to_string(),
+            "gpt-4".to_string(),
+            "desc".to_string(),
+            "prompt".to_string(),
+        );
+        assert_eq!(model.summarize(&[serde_json::json!({"title": "t"})]).await.unwrap(), Digest::default());
+        completions.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_openai_error() {
+        let mut server = mockito::Server::new_async().await;
+        let _completions = server.mock("POST", "/v1/chat/completions")
+            .with_status(400)
+            .with_body(r#"{"error":{"message":"Invalid schema for response_format","type":"invalid_request_error"}}"#)
+            .create_async()
+            .await;
+
+        let model = Model::new(
+            server.url(),
+            "key".to_string(),
+            "gpt-4".to_string(),
+            "desc".to_string(),
+            "prompt".to_string(),
+        );
+        let error = model.summarize(&[serde_json::json!({"title": "t"})]).await.unwrap_err();
+        assert!(error.to_string().contains("400 Bad Request: Invalid schema for response_format"));
+    }
+
+    #[tokio::test]
+    async fn test_openai_error_without_json() {
+        let mut server = mockito::Server::new_async().await;
+        let completions = server.mock("POST", "/v1/chat/completions")
+            .with_status(502)
+            .with_header("content-type", "text/html")
+            .with_body("<html><body>Bad Gateway</body></html>\n")
+            .expect(1)
+            .create_async()
+            .await;
+
+        let model = Model::new(
+            server.url(),
+            "key".to_string(),
+            "gpt-4".to_string(),
+            "desc".to_string(),
+            "prompt".to_string(),
+        )
+        .with_retry(RetryPolicy::none());
+        let error = model.summarize(&[serde_json::json!({"title": "t"})]).await.unwrap_err();
+        assert_eq!(error.to_string(), "OpenAI error 502 Bad Gateway: <html><body>Bad Gateway</body></html>");
+        completions.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_anthropic_rate_limit_in_plain_text() {
+        let mut server = mockito::Server::new_async().await;
+        let _messages = server.mock("POST", "/v1/messages")
+            .with_status(429)
+            .with_body("Too many requests, slow down")
+            .create_async()
+            .await;
+
+        let error = anthropic(server.url()).summarize(&[serde_json::json!({"title": "t"})]).await.unwrap_err();
+        assert!(error.to_string().contains("429 Too Many Requests: Too many requests, slow down"));
+    }
+
+    struct EchoBackend;
+
+    #[async_trait::async_trait]
+    impl ChatBackend for EchoBackend {
+        fn name(&self) -> &str {
+            "echo"
+        }
+
+        async fn chat(&self, request: &ChatRequest<'_>) -> Result<String, crate::models::CustomError> {
+            assert_eq!(request.system, "desc");
+            assert_eq!(request.messages.len(), 1);
+            Ok(r#"{"news":[]}"#.to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_summarize_with_custom_backend() {
+        let model = Model::new(
+            "url".to_string(),
+            "key".to_string(),
+            "model".to_string(),
+            "desc".to_string(),
+            "prompt".to_string(),
+        )
+        .with_backend(Box::new(EchoBackend));
+        assert!(model.fingerprint().starts_with("echo\n"));
+        let digest = model.summarize(&[serde_json::json!({"title": "t"})]).await.unwrap();
+        assert_eq!(digest, Digest::default());
+    }
+}
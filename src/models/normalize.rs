@@ -0,0 +1,573 @@
+use std::str::FromStr;
+
+// Rough average for latin languages, good enough to size prompts
+pub const CHARS_PER_TOKEN: usize = 4;
+
+// Elements whose whole content is boilerplate for a summary
+const SKIPPED: &[&str] = &[
+    "script", "style", "noscript", "iframe", "svg", "head", "template", "nav", "footer", "aside",
+    "form", "button", "select", "object", "video", "audio", "canvas",
+];
+
+const BLOCKS: &[&str] = &[
+    "p", "div", "section", "article", "header", "main", "blockquote", "pre", "ul", "ol", "li",
+    "table", "tr", "dl", "dt", "dd", "figure", "figcaption", "hr", "br", "h1", "h2", "h3", "h4",
+    "h5", "h6",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentFormat {
+    Html,
+    Text,
+    Markdown,
+}
+
+impl FromStr for ContentFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "html" => Ok(ContentFormat::Html),
+            "text" => Ok(ContentFormat::Text),
+            "markdown" | "md" => Ok(ContentFormat::Markdown),
+            _ => Err(format!("Unknown content format: {}", value)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Normalizer {
+    pub format: ContentFormat,
+    pub max_chars: Option<usize>,
+}
+
+impl Default for Normalizer {
+    fn default() -> Self {
+        Normalizer {
+            format: ContentFormat::Markdown,
+            max_chars: None,
+        }
+    }
+}
+
+impl Normalizer {
+    pub fn new(format: ContentFormat) -> Self {
+        Normalizer {
+            format,
+            max_chars: None,
+        }
+    }
+
+    pub fn with_max_chars(mut self, max_chars: usize) -> Self {
+        self.max_chars = Some(max_chars);
+        self
+    }
+
+    pub fn with_max_tokens(self, max_tokens: usize) -> Self {
+        self.with_max_chars(max_tokens * CHARS_PER_TOKEN)
+    }
+
+    pub fn normalize(&self, html: &str) -> String {
+        let content = match self.format {
+            ContentFormat::Html => html.to_string(),
+            ContentFormat::Text => Converter::new(false).convert(html),
+            ContentFormat::Markdown => Converter::new(true).convert(html),
+        };
+        match self.max_chars {
+            Some(max_chars) => truncate(&content, max_chars),
+            None => content,
+        }
+    }
+}
+
+// Tokens a text will roughly take in a prompt
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(CHARS_PER_TOKEN)
+}
+
+// Cuts at a word boundary and marks the cut with an ellipsis
+pub fn truncate(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let cut: String = text.chars().take(max_chars.saturating_sub(1)).collect();
+    let cut = match cut.rfind(char::is_whitespace) {
+        Some(index) if index > cut.len() / 2 => &cut[..index],
+        _ => cut.as_str(),
+    };
+    format!("{}…", cut.trim_end())
+}
+
+struct Tag {
+    name: String,
+    closing: bool,
+    self_closing: bool,
+    attributes: Vec<(String, String)>,
+}
+
+impl Tag {
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+struct Converter {
+    markdown: bool,
+    output: String,
+    skip: Vec<String>,
+    links: Vec<(usize, Option<String>)>,
+    lists: Vec<Option<usize>>,
+    pre: usize,
+    // Whitespace seen but not written yet
+    space: bool,
+}
+
+impl Converter {
+    fn new(markdown: bool) -> Self {
+        Converter {
+            markdown,
+            output: String::new(),
+            skip: Vec::new(),
+            links: Vec::new(),
+            lists: Vec::new(),
+            pre: 0,
+            space: false,
+        }
+    }
+
+    fn convert(mut self, html: &str) -> String {
+        let mut rest = html;
+        while !rest.is_empty() {
+            match rest.find('<') {
+                Some(0) => {
+                    rest = if let Some(comment) = rest.strip_prefix("<!--") {
+                        comment.find("-->").map(|end| &comment[end + 3..]).unwrap_or("")
+                    } else if let Some(cdata) = rest.strip_prefix("<![CDATA[") {
+                        let end = cdata.find("]]>").unwrap_or(cdata.len());
+                        self.text(&cdata[..end], false);
+                        cdata.get(end + 3..).unwrap_or("")
+                    } else {
+                        match parse_tag(rest) {
+                            Some((tag, remaining)) => {
+                                self.tag(tag);
+                                remaining
+                            }
+                            None => {
+                                self.text("<", true);
+                                &rest[1..]
+                            }
+                        }
+                    };
+                }
+                Some(index) => {
+                    self.text(&rest[..index], true);
+                    rest = &rest[index..];
+                }
+                None => {
+                    self.text(rest, true);
+                    rest = "";
+                }
+            }
+        }
+        self.finish()
+    }
+
+    fn text(&mut self, text: &str, escaped: bool) {
+        if !self.skip.is_empty() {
+            return;
+        }
+        let text = if escaped { decode_entities(text) } else { text.to_string() };
+        if self.pre > 0 {
+            self.output.push_str(&text);
+            return;
+        }
+        let mut space = self.space || text.starts_with(char::is_whitespace);
+        for word in text.split_whitespace() {
+            if space {
+                self.separate();
+            }
+            self.output.push_str(word);
+            space = true;
+        }
+        self.space = match text.trim_end().is_empty() {
+            true => self.space || !text.is_empty(),
+            false => text.ends_with(char::is_whitespace),
+        };
+    }
+
+    fn separate(&mut self) {
+        if !self.output.is_empty() && !self.output.ends_with(['\n', ' ']) {
+            self.output.push(' ');
+        }
+        self.space = false;
+    }
+
+    fn newlines(&mut self, count: usize) {
+        self.space = false;
+        while self.output.ends_with(' ') {
+            self.output.pop();
+        }
+        if self.output.is_empty() {
+            return;
+        }
+        let existing = self.output.chars().rev().take_while(|c| *c == '\n').count();
+        for _ in existing..count {
+            self.output.push('\n');
+        }
+    }
+
+    fn tag(&mut self, tag: Tag) {
+        let name = tag.name.as_str();
+        if let Some(skipped) = self.skip.last() {
+            if tag.closing && skipped == name {
+                self.skip.pop();
+            } else if !tag.closing && !tag.self_closing && name == skipped {
+                self.skip.push(name.to_string());
+            }
+            return;
+        }
+        if SKIPPED.contains(&name) {
+            if !tag.closing && !tag.self_closing {
+                self.skip.push(name.to_string());
+            }
+            return;
+        }
+        if BLOCKS.contains(&name) {
+            let count = match name {
+                "br" | "li" | "tr" | "dt" | "dd" => 1,
+                "pre" if tag.closing && self.markdown => 1,
+                _ => 2,
+            };
+            self.newlines(count);
+        }
+        match (name, tag.closing) {
+            ("h1" | "h2" | "h3" | "h4" | "h5" | "h6", false) if self.markdown => {
+                let level = name[1..].parse::<usize>().unwrap_or(1);
+                self.output.push_str(&"#".repeat(level));
+                self.output.push(' ');
+            }
+            ("ul", false) => self.lists.push(None),
+            ("ol", false) => self.lists.push(Some(0)),
+            ("ul" | "ol", true) => {
+                self.lists.pop();
+            }
+            ("li", false) => {
+                let indent = "  ".repeat(self.lists.len().saturating_sub(1));
+                self.output.push_str(&indent);
+                match self.lists.last_mut() {
+                    Some(Some(number)) => {
+                        *number += 1;
+                        self.output.push_str(&format!("{}. ", number));
+                    }
+                    _ => self.output.push_str("- "),
+                }
+            }
+            ("pre", false) => {
+                self.pre += 1;
+                if self.markdown {
+                    self.output.push_str("```\n");
+                }
+            }
+            ("pre", true) => {
+                self.pre = self.pre.saturating_sub(1);
+                if self.markdown {
+                    self.newlines(1);
+                    self.output.push_str("```");
+                    self.newlines(2);
+                }
+            }
+            ("blockquote", false) if self.markdown => self.output.push_str("> "),
+            ("hr", _) if self.markdown => {
+                self.output.push_str("---");
+                self.newlines(2);
+            }
+            ("strong" | "b", _) if self.markdown => self.inline("**", tag.closing),
+            ("em" | "i", _) if self.markdown => self.inline("*", tag.closing),
+            ("code", _) if self.markdown && self.pre == 0 => self.inline("`", tag.closing),
+            ("a", false) => {
+                let href = tag
+                    .attribute("href")
+                    .filter(|href| href.starts_with("http://") || href.starts_with("https://"))
+                    .map(decode_entities);
+                self.inline("", false);
+                self.links.push((self.output.len(), href));
+            }
+            ("a", true) => self.close_link(),
+            _ => {}
+        }
+    }
+
+    fn inline(&mut self, marker: &str, closing: bool) {
+        if !closing && self.space {
+            self.separate();
+        }
+        self.output.push_str(marker);
+    }
+
+    fn close_link(&mut self) {
+        let Some((start, href)) = self.links.pop() else {
+            return;
+        };
+        let Some(href) = href else {
+            return;
+        };
+        // Empty links and linked images render no text, nothing to trim past
+        let start = start.min(self.output.len());
+        let text = self.output[start..].trim().to_string();
+        self.output.truncate(start);
+        let link = if text.is_empty() {
+            href
+        } else if self.markdown {
+            format!("[{}]({})", text, href)
+        } else if text == href {
+            text
+        } else {
+            format!("{} ({})", text, href)
+        };
+        self.output.push_str(&link);
+    }
+
+    fn finish(self) -> String {
+        let mut result = String::new();
+        let mut blank = 0;
+        for line in self.output.lines() {
+            let line = line.trim_end();
+            if line.is_empty() {
+                blank += 1;
+                continue;
+            }
+            if !result.is_empty() {
+                result.push_str(if blank > 0 { "\n\n" } else { "\n" });
+            }
+            blank = 0;
+            result.push_str(line);
+        }
+        result
+    }
+}
+
+fn parse_tag(input: &str) -> Option<(Tag, &str)> {
+    let body = &input[1..];
+    let (closing, body) = match body.strip_prefix('/') {
+        Some(body) => (true, body),
+        None => (false, body),
+    };
+    if body.starts_with('!') || body.starts_with('?') {
+        let end = body.find('>')?;
+        let tag = Tag {
+            name: String::new(),
+            closing: true,
+            self_closing: true,
+            attributes: Vec::new(),
+        };
+        return Some((tag, &body[end + 1..]));
+    }
+    let name_end = body.find(|c: char| !c.is_ascii_alphanumeric())?;
+    if name_end == 0 {
+        return None;
+    }
+    let name = body[..name_end].to_lowercase();
+    let mut attributes = Vec::new();
+    let mut rest = &body[name_end..];
+    loop {
+        rest = rest.trim_start();
+        if let Some(remaining) = rest.strip_prefix("/>") {
+            return Some((Tag { name, closing, self_closing: true, attributes }, remaining));
+        }
+        if let Some(remaining) = rest.strip_prefix('>') {
+            return Some((Tag { name, closing, self_closing: false, attributes }, remaining));
+        }
+        if rest.is_empty() {
+            return None;
+        }
+        let key_end = rest
+            .find(|c: char| c.is_whitespace() || c == '=' || c == '>' || c == '/')
+            .unwrap_or(rest.len());
+        let key = rest[..key_end].to_lowercase();
+        rest = rest[key_end..].trim_start();
+        let mut value = String::new();
+        if let Some(remaining) = rest.strip_prefix('=') {
+            let remaining = remaining.trim_start();
+            let (parsed, remaining) = match remaining.chars().next() {
+                Some(quote @ ('"' | '\'')) => {
+                    let end = remaining[1..].find(quote)? + 1;
+                    (&remaining[1..end], &remaining[end + 1..])
+                }
+                _ => {
+                    let end = remaining
+                        .find(|c: char| c.is_whitespace() || c == '>')
+                        .unwrap_or(remaining.len());
+                    (&remaining[..end], &remaining[end..])
+                }
+            };
+            value = parsed.to_string();
+            rest = remaining;
+        } else if key.is_empty() {
+            // Stray character, skip it so we always make progress
+            rest = &rest[rest.chars().next().map(char::len_utf8).unwrap_or(1)..];
+            continue;
+        }
+        attributes.push((key, value));
+    }
+}
+
+pub fn decode_entities(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let decoded = rest[1..].find(';').filter(|end| *end <= 10).and_then(|end| {
+            let entity = &rest[1..end + 1];
+            let decoded = match entity {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some(' '),
+                "hellip" => Some('…'),
+                "mdash" => Some('—'),
+                "ndash" => Some('–'),
+                "lsquo" => Some('‘'),
+                "rsquo" => Some('’'),
+                "ldquo" => Some('“'),
+                "rdquo" => Some('”'),
+                "laquo" => Some('«'),
+                "raquo" => Some('»'),
+                "copy" => Some('©'),
+                _ => entity
+                    .strip_prefix("#x")
+                    .or_else(|| entity.strip_prefix("#X"))
+                    .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                    .or_else(|| entity.strip_prefix('#').and_then(|dec| dec.parse::<u32>().ok()))
+                    .and_then(char::from_u32),
+            };
+            decoded.map(|c| (c, end + 2))
+        });
+        match decoded {
+            Some((c, length)) => {
+                result.push(c);
+                rest = &rest[length..];
+            }
+            None => {
+                result.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::{decode_entities, estimate_tokens, truncate, ContentFormat, Normalizer};
+
+    const ARTICLE: &str = r#"<div class="post">
+<script>track();</script><style>p { color: red }</style>
+<h2>Big &amp; news</h2>
+<p>Read <a href="https://example.org/more?a=1&amp;b=2">the <b>full</b> story</a> today.</p>
+<img src="https://tracker.example/pixel.gif" width="1" height="1">
+<ul><li>One</li><li>Two</li></ul>
+<nav><a href="/home">Home</a></nav>
+</div>"#;
+
+    #[test]
+    fn test_markdown() {
+        let normalizer = Normalizer::new(ContentFormat::Markdown);
+        assert_eq!(
+            normalizer.normalize(ARTICLE),
+            "## Big & news\n\nRead [the **full** story](https://example.org/more?a=1&b=2) today.\n\n- One\n- Two"
+        );
+    }
+
+    #[test]
+    fn test_text() {
+        let normalizer = Normalizer::new(ContentFormat::Text);
+        assert_eq!(
+            normalizer.normalize(ARTICLE),
+            "Big & news\n\nRead the full story (https://example.org/more?a=1&b=2) today.\n\n- One\n- Two"
+        );
+    }
+
+    #[test]
+    fn test_html_is_untouched() {
+        let normalizer = Normalizer::new(ContentFormat::Html);
+        assert_eq!(normalizer.normalize(ARTICLE), ARTICLE);
+    }
+
+    #[test]
+    fn test_plain_text_input() {
+        let normalizer = Normalizer::default();
+        assert_eq!(normalizer.normalize("  just   text\n here "), "just text here");
+        assert_eq!(normalizer.normalize("a < b"), "a < b");
+    }
+
+    #[test]
+    fn test_ordered_list_and_pre() {
+        let normalizer = Normalizer::new(ContentFormat::Markdown);
+        assert_eq!(
+            normalizer.normalize("<ol><li>First</li><li>Second</li></ol><pre>let x = 1;\nlet y = 2;</pre>"),
+            "1. First\n2. Second\n\n```\nlet x = 1;\nlet y = 2;\n```"
+        );
+    }
+
+    #[test]
+    fn test_relative_links_keep_text_only() {
+        let normalizer = Normalizer::new(ContentFormat::Markdown);
+        assert_eq!(normalizer.normalize(r#"<p>See <a href="/about">about</a>.</p>"#), "See about.");
+    }
+
+    #[test]
+    fn test_empty_links() {
+        let markdown = Normalizer::new(ContentFormat::Markdown);
+        assert_eq!(markdown.normalize(r#"foo <a href="https://x.example"></a>"#), "foo https://x.example");
+        assert_eq!(
+            markdown.normalize(r#"foo <a href="https://x.example"> </a> bar"#),
+            "foo https://x.example bar"
+        );
+        let text = Normalizer::new(ContentFormat::Text);
+        assert_eq!(text.normalize(r#"foo <a href="https://x.example"></a>"#), "foo https://x.example");
+    }
+
+    #[test]
+    fn test_linked_image() {
+        let normalizer = Normalizer::new(ContentFormat::Markdown);
+        assert_eq!(
+            normalizer.normalize(r#"<p>hi <a href="https://x.example"><img src=x></a></p>"#),
+            "hi https://x.example"
+        );
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("short", 10), "short");
+        assert_eq!(truncate("one two three four", 12), "one two…");
+        let normalizer = Normalizer::new(ContentFormat::Text).with_max_tokens(2);
+        assert_eq!(normalizer.normalize("<p>alpha beta gamma</p>"), "alpha…");
+    }
+
+    #[test]
+    fn test_decode_entities() {
+        assert_eq!(decode_entities("&lt;a&gt; &#38; &#x41; &unknown; &"), "<a> & A &unknown; &");
+    }
+
+    #[test]
+    fn test_estimate_tokens() {
+        assert_eq!(estimate_tokens(""), 0);
+        assert_eq!(estimate_tokens("abcd"), 1);
+        assert_eq!(estimate_tokens("abcde"), 2);
+        assert_eq!(estimate_tokens("ñáéíóú"), 2);
+    }
+
+    #[test]
+    fn test_content_format_from_str() {
+        assert_eq!("MD".parse::<ContentFormat>(), Ok(ContentFormat::Markdown));
+        assert_eq!("text".parse::<ContentFormat>(), Ok(ContentFormat::Text));
+        assert!("pdf".parse::<ContentFormat>().is_err());
+    }
+}
@@ -0,0 +1,340 @@
+use async_trait::async_trait;
+use tracing::warn;
+
+use super::{truncate, CustomError, Icon};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Markup {
+    Plain,
+    Html,
+    MarkdownV2,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    // Longest message the channel accepts, in chars
+    pub max_length: usize,
+    pub markup: Markup,
+    // Whether feed icons are worth fetching for this channel
+    pub icons: bool,
+}
+
+// One summarized news item, ready to be rendered for any channel
+#[derive(Debug, Clone, Default)]
+pub struct Notification {
+    pub title: String,
+    pub url: String,
+    pub summary: String,
+    // Label and URL of attached media, like the lead image or a podcast
+    pub links: Vec<(String, String)>,
+    pub icon: Option<Icon>,
+}
+
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn capabilities(&self) -> Capabilities;
+
+    // Sends an already rendered message
+    async fn send(&self, message: &str) -> Result<(), CustomError>;
+
+    async fn render(&self, item: &Notification) -> String {
+        render(item, self.capabilities().markup)
+    }
+
+    // Renders the item shortening its summary until it fits in a message,
+    // an oversized item would be rejected on every cycle
+    async fn render_within_limit(&self, item: &Notification) -> String {
+        let max_length = self.capabilities().max_length;
+        let mut item = item.clone();
+        let mut rendered = self.render(&item).await;
+        loop {
+            let length = rendered.chars().count();
+            let summary_length = item.summary.chars().count();
+            if length <= max_length || summary_length == 0 {
+                break;
+            }
+            let excess = length - max_length;
+            let summary = truncate(&item.summary, summary_length.saturating_sub(excess + 1));
+            // Nothing left but the ellipsis
+            item.summary = if summary.chars().count() >= summary_length { String::new() } else { summary };
+            rendered = self.render(&item).await;
+        }
+        if rendered.chars().count() > max_length {
+            warn!("{} is too long for {} even without summary", item.url, self.name());
+        }
+        rendered
+    }
+
+    async fn send_item(&self, item: &Notification) -> Result<(), CustomError> {
+        let message = self.render_within_limit(item).await;
+        self.send(&message).await
+    }
+
+    // Packs as many items as fit in each message
+    async fn send_digest(&self, items: &[Notification]) -> Result<(), CustomError> {
+        let max_length = self.capabilities().max_length;
+        let mut messages = Vec::new();
+        let mut current = String::new();
+        for item in items {
+            let rendered = self.render_within_limit(item).await;
+            if !current.is_empty() && current.chars().count() + rendered.chars().count() > max_length {
+                messages.push(std::mem::take(&mut current));
+            }
+            current.push_str(&rendered);
+        }
+        if !current.is_empty() {
+            messages.push(current);
+        }
+        for message in messages {
+            self.send(&message).await?;
+        }
+        Ok(())
+    }
+}
+
+pub fn render(item: &Notification, markup: Markup) -> String {
+    match markup {
+        Markup::Html => {
+            let links = item
+                .links
+                .iter()
+                .map(|(label, link)| {
+                    let link = escape_html(link);
+                    format!("<p>{}: <a href=\"{}\">{}</a></p>", escape_html(label), link, link)
+                })
+                .collect::<String>();
+            format!(
+                "<h3><a href=\"{}\">{}</a></h3><p>{}</p>{}<br>",
+                escape_html(&item.url),
+                escape_html(&item.title),
+                escape_html(&item.summary),
+                links
+            )
+        }
+        Markup::MarkdownV2 => {
+            let links = item
+                .links
+                .iter()
+                .map(|(label, link)| format!("[{}]({})\n", escape(label), escape(link)))
+                .collect::<String>();
+            format!(
+                "[{}]({})\n{}\n{}\n",
+                escape(&item.title),
+                escape(&item.url),
+                escape(&item.summary),
+                links
+            )
+        }
+        Markup::Plain => {
+            let links = item
+                .links
+                .iter()
+                .map(|(label, link)| format!("{}: {}\n", label, link))
+                .collect::<String>();
+            format!("{}\n{}\n{}\n{}\n", item.title, item.url, item.summary, links)
+        }
+    }
+}
+
+// Model output is plain text, keep it from breaking the markup
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// Telegram MarkdownV2 reserved characters
+pub fn escape(text: &str) -> String {
+    let reserved = r#"_*[]()~`>#+-=|{}.!\\"#;
+    let mut escaped = String::new();
+    for c in text.chars() {
+        if reserved.contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    struct Recorder {
+        max_length: usize,
+        sent: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl Notifier for Recorder {
+        fn name(&self) -> &str {
+            "recorder"
+        }
+
+        fn capabilities(&self) -> Capabilities {
+            Capabilities {
+                max_length: self.max_length,
+                markup: Markup::Plain,
+                icons: false,
+            }
+        }
+
+        async fn send(&self, message: &str) -> Result<(), CustomError> {
+            self.sent.lock().unwrap().push(message.to_string());
+            Ok(())
+        }
+    }
+
+    fn item(title: &str) -> Notification {
+        Notification {
+            title: title.to_string(),
+            url: format!("https://example.org/{}", title),
+            summary: "Summary".to_string(),
+            ..Notification::default()
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        let mut item = item("a.b");
+        item.links.push(("Listen".to_string(), "https://example.org/a.mp3".to_string()));
+        assert_eq!(
+            render(&item, Markup::Html),
+            "<h3><a href=\"https://example.org/a.b\">a.b</a></h3><p>Summary</p><p>Listen: <a href=\"https://example.org/a.mp3\">https://example.org/a.mp3</a></p><br>"
+        );
+        assert_eq!(
+            render(&item, Markup::MarkdownV2),
+            "[a\\.b](https://example\\.org/a\\.b)\nSummary\n[Listen](https://example\\.org/a\\.mp3)\n\n"
+        );
+        assert_eq!(
+            render(&item, Markup::Plain),
+            "a.b\nhttps://example.org/a.b\nSummary\nListen: https://example.org/a.mp3\n\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_send_digest_splits_long_digests() {
+        let notifier = Recorder {
+            max_length: 80,
+            sent: Mutex::new(Vec::new()),
+        };
+        notifier.send_digest(&[item("one"), item("two"), item("three")]).await.unwrap();
+        let sent = notifier.sent.lock().unwrap();
+        assert_eq!(sent.len(), 2);
+        assert!(sent[0].starts_with("one") && sent[0].contains("two"));
+        assert!(sent[1].starts_with("three"));
+    }
+
+    #[tokio::test]
+    async fn test_send_item() {
+        let notifier = Recorder {
+            max_length: 10,
+            sent: Mutex::new(Vec::new()),
+        };
+        notifier.send_item(&item("one")).await.unwrap();
+        notifier.send_digest(&[]).await.unwrap();
+        assert_eq!(notifier.sent.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_render_escapes_html() {
+        let item = Notification {
+            title: "Rust <3 & C".to_string(),
+            url: "https://example.org/?a=1&b=\"2\"".to_string(),
+            summary: "1 < 2 > 0".to_string(),
+            ..Notification::default()
+        };
+        assert_eq!(
+            render(&item, Markup::Html),
+            "<h3><a href=\"https://example.org/?a=1&amp;b=&quot;2&quot;\">Rust &lt;3 &amp; C</a></h3><p>1 &lt; 2 &gt; 0</p><br>"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_oversized_items_are_truncated() {
+        let notifier = Recorder {
+            max_length: 100,
+            sent: Mutex::new(Vec::new()),
+        };
+        let mut long = item("long");
+        long.summary = "word ".repeat(100);
+        notifier.send_digest(&[item("one"), long.clone()]).await.unwrap();
+        notifier.send_item(&long).await.unwrap();
+        let sent = notifier.sent.lock().unwrap();
+        assert_eq!(sent.len(), 3);
+        assert!(sent.iter().all(|message| message.chars().count() <= 100));
+        assert!(sent[1].starts_with("long\nhttps://example.org/long\nword word"));
+        assert!(sent[1].contains('…'));
+    }
+
+    #[test]
+    fn test_escape_simple_text() {
+        let text = "hello world";
+        let result = escape(text);
+        assert_eq!(result, "hello world");
+    }
+
+    #[test]
+    fn test_escape_text_with_markdown_chars() {
+        let text = "hello*world";
+        let result = escape(text);
+        assert_eq!(result, "hello\\*world");
+    }
+
+    #[test]
+    fn test_escape_text_with_multiple_special_chars() {
+        let text = "_bold_ *italic* [link](url)";
+        let result = escape(text);
+        assert_eq!(result, "\\_bold\\_ \\*italic\\* \\[link\\]\\(url\\)");
+    }
+
+    #[test]
+    fn test_escape_empty_string() {
+        let text = "";
+        let result = escape(text);
+        assert_eq!(result, "");
+    }
+
+    #[test]
+    fn test_escape_all_special_chars() {
+        let text = "_*[]()~`>#+-=|{}.!\\";
+        let result = escape(text);
+        assert_eq!(
+            result,
+            "\\_\\*\\[\\]\\(\\)\\~\\`\\>\\#\\+\\-\\=\\|\\{\\}\\.\\!\\\\"
+        );
+    }
+
+    #[test]
+    fn test_escape_mixed_content() {
+        let text = "Price: $100 + tax";
+        let result = escape(text);
+        assert_eq!(result, "Price: $100 \\+ tax");
+    }
+
+    #[test]
+    fn test_escape_backslash() {
+        let text = "C:\\Users\\test";
+        let result = escape(text);
+        assert_eq!(result, "C:\\\\Users\\\\test");
+    }
+
+    #[test]
+    fn test_escape_complex_markdown() {
+        let text = "**bold** _italic_ [link](url)";
+        let result = escape(text);
+        assert_eq!(result, "\\*\\*bold\\*\\* \\_italic\\_ \\[link\\]\\(url\\)");
+    }
+}
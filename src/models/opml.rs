@@ -0,0 +1,345 @@
+use quick_xml::{
+    escape::{escape, unescape},
+    events::{BytesStart, Event},
+    Reader,
+};
+use std::{collections::HashMap, fmt};
+
+use super::Feed;
+
+const DEFAULT_CATEGORY: &str = "All";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpmlFeed {
+    pub title: String,
+    pub xml_url: String,
+    pub html_url: String,
+    pub category: Option<String>,
+}
+
+impl OpmlFeed {
+    // Top level feeds end up in Miniflux's default category
+    pub fn category_title(&self) -> &str {
+        self.category.as_deref().unwrap_or(DEFAULT_CATEGORY)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Opml {
+    pub title: String,
+    pub feeds: Vec<OpmlFeed>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OpmlDiff {
+    // In the desired document but not subscribed yet
+    pub added: Vec<OpmlFeed>,
+    // Subscribed but missing from the desired document
+    pub removed: Vec<OpmlFeed>,
+    // Subscribed in both, but under a different category
+    pub moved: Vec<OpmlFeed>,
+}
+
+#[derive(Debug)]
+pub struct OpmlError(String);
+
+impl fmt::Display for OpmlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid OPML document: {}", self.0)
+    }
+}
+
+impl std::error::Error for OpmlError {}
+
+impl From<quick_xml::Error> for OpmlError {
+    fn from(error: quick_xml::Error) -> Self {
+        OpmlError(error.to_string())
+    }
+}
+
+impl OpmlDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.moved.is_empty()
+    }
+}
+
+impl Opml {
+    pub fn from_feeds(title: &str, feeds: &[Feed]) -> Self {
+        Opml {
+            title: title.to_string(),
+            feeds: feeds
+                .iter()
+                .map(|feed| OpmlFeed {
+                    title: feed.title.clone(),
+                    xml_url: feed.feed_url.clone(),
+                    html_url: feed.site_url.clone(),
+                    category: feed.category.as_ref().map(|category| category.title.clone()),
+                })
+                .collect(),
+        }
+    }
+
+    pub fn parse(document: &str) -> Result<Self, OpmlError> {
+        let mut reader = Reader::from_str(document);
+        let mut opml = Opml::default();
+        let mut seen_root = false;
+        let mut in_title = false;
+        // Text of every open <outline>, `None` for the ones that are feeds
+        let mut outlines: Vec<Option<String>> = Vec::new();
+        loop {
+            match reader.read_event()? {
+                Event::Start(element) => match element.name().as_ref() {
+                    b"opml" => seen_root = true,
+                    b"title" => in_title = outlines.is_empty(),
+                    b"outline" => {
+                        let outline = Self::read_outline(&element, &outlines)?;
+                        outlines.push(match outline {
+                            Outline::Feed(feed) => {
+                                opml.feeds.push(feed);
+                                None
+                            }
+                            Outline::Group(text) => Some(text),
+                        });
+                    }
+                    _ => {}
+                },
+                Event::Empty(element) => match element.name().as_ref() {
+                    b"opml" => seen_root = true,
+                    b"outline" => {
+                        if let Outline::Feed(feed) = Self::read_outline(&element, &outlines)? {
+                            opml.feeds.push(feed);
+                        }
+                    }
+                    _ => {}
+                },
+                Event::End(element) => match element.name().as_ref() {
+                    b"title" => in_title = false,
+                    b"outline" => {
+                        outlines.pop();
+                    }
+                    _ => {}
+                },
+                Event::Text(text) if in_title => {
+                    let text = text.decode().map_err(|e| OpmlError(e.to_string()))?;
+                    opml.title.push_str(&text);
+                }
+                Event::GeneralRef(reference) if in_title => {
+                    let name = reference.decode().map_err(|e| OpmlError(e.to_string()))?;
+                    let reference = format!("&{};", name);
+                    let text = unescape(&reference).map_err(|e| OpmlError(e.to_string()))?;
+                    opml.title.push_str(&text);
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+        if !seen_root {
+            return Err(OpmlError("missing <opml> root element".to_string()));
+        }
+        opml.title = opml.title.trim().to_string();
+        Ok(opml)
+    }
+
+    fn read_outline(element: &BytesStart, parents: &[Option<String>]) -> Result<Outline, OpmlError> {
+        let mut attributes = HashMap::new();
+        for attribute in element.attributes() {
+            let attribute = attribute.map_err(|e| OpmlError(e.to_string()))?;
+            let key = String::from_utf8_lossy(attribute.key.as_ref()).to_string();
+            attributes.insert(key, attribute.unescape_value()?.to_string());
+        }
+        let text = attributes
+            .get("title")
+            .or_else(|| attributes.get("text"))
+            .cloned()
+            .unwrap_or_default();
+        match attributes.get("xmlUrl") {
+            Some(xml_url) => Ok(Outline::Feed(OpmlFeed {
+                title: text,
+                xml_url: xml_url.clone(),
+                html_url: attributes.get("htmlUrl").cloned().unwrap_or_default(),
+                category: parents.iter().rev().flatten().next().cloned(),
+            })),
+            None => Ok(Outline::Group(text)),
+        }
+    }
+
+    pub fn to_xml(&self) -> String {
+        let mut categories: Vec<(&str, Vec<&OpmlFeed>)> = Vec::new();
+        for feed in self.feeds.iter() {
+            let category = feed.category_title();
+            match categories.iter_mut().find(|(name, _)| *name == category) {
+                Some((_, feeds)) => feeds.push(feed),
+                None => categories.push((category, vec![feed])),
+            }
+        }
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        xml.push_str("<opml version=\"2.0\">\n");
+        xml.push_str(&format!("    <head>\n        <title>{}</title>\n    </head>\n", escape(&self.title)));
+        xml.push_str("    <body>\n");
+        for (category, feeds) in categories {
+            xml.push_str(&format!("        <outline text=\"{}\">\n", escape(category)));
+            for feed in feeds {
+                xml.push_str(&format!(
+                    "            <outline title=\"{}\" text=\"{}\" xmlUrl=\"{}\" htmlUrl=\"{}\"></outline>\n",
+                    escape(&feed.title),
+                    escape(&feed.title),
+                    escape(&feed.xml_url),
+                    escape(&feed.html_url),
+                ));
+            }
+            xml.push_str("        </outline>\n");
+        }
+        xml.push_str("    </body>\n</opml>\n");
+        xml
+    }
+
+    // Changes needed to go from `self` (current) to `desired`, matched by feed URL
+    pub fn diff(&self, desired: &Opml) -> OpmlDiff {
+        let current: HashMap<&str, &OpmlFeed> = self
+            .feeds
+            .iter()
+            .map(|feed| (feed.xml_url.as_str(), feed))
+            .collect();
+        let wanted: HashMap<&str, &OpmlFeed> = desired
+            .feeds
+            .iter()
+            .map(|feed| (feed.xml_url.as_str(), feed))
+            .collect();
+        let mut diff = OpmlDiff::default();
+        for feed in desired.feeds.iter() {
+            match current.get(feed.xml_url.as_str()) {
+                None => diff.added.push(feed.clone()),
+                // Miniflux matches category titles ignoring case too
+                Some(existing) if !existing.category_title().eq_ignore_ascii_case(feed.category_title()) => {
+                    diff.moved.push(feed.clone())
+                }
+                Some(_) => {}
+            }
+        }
+        for feed in self.feeds.iter() {
+            if !wanted.contains_key(feed.xml_url.as_str()) {
+                diff.removed.push(feed.clone());
+            }
+        }
+        diff
+    }
+}
+
+enum Outline {
+    Feed(OpmlFeed),
+    Group(String),
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Opml, OpmlFeed};
+
+    const DOCUMENT: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<opml version="2.0">
+    <head>
+        <title>Miniflux &amp; friends</title>
+    </head>
+    <body>
+        <outline text="Tech">
+            <outline title="Rust Blog" text="Rust Blog" xmlUrl="https://blog.rust-lang.org/feed.xml" htmlUrl="https://blog.rust-lang.org/"></outline>
+            <outline title="Q&amp;A" text="Q&amp;A" xmlUrl="https://example.org/qa.xml" htmlUrl="https://example.org/"/>
+        </outline>
+        <outline text="Loose" xmlUrl="https://example.org/loose.xml"/>
+    </body>
+</opml>"#;
+
+    fn feed(url: &str, category: Option<&str>) -> OpmlFeed {
+        OpmlFeed {
+            title: url.to_string(),
+            xml_url: url.to_string(),
+            html_url: String::new(),
+            category: category.map(|category| category.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_parse_opml() {
+        let opml = Opml::parse(DOCUMENT).unwrap();
+        assert_eq!(opml.title, "Miniflux & friends");
+        assert_eq!(opml.feeds.len(), 3);
+        assert_eq!(opml.feeds[0].title, "Rust Blog");
+        assert_eq!(opml.feeds[0].category.as_deref(), Some("Tech"));
+        assert_eq!(opml.feeds[1].title, "Q&A");
+        assert_eq!(opml.feeds[1].category.as_deref(), Some("Tech"));
+        assert_eq!(opml.feeds[2].title, "Loose");
+        assert_eq!(opml.feeds[2].category, None);
+    }
+
+    #[test]
+    fn test_parse_invalid_document() {
+        assert!(Opml::parse("<html></html>").is_err());
+        assert!(Opml::parse("<opml><body><outline></body></opml>").is_err());
+    }
+
+    #[test]
+    fn test_write_and_parse_roundtrip() {
+        let opml = Opml {
+            title: "Subscriptions <test>".to_string(),
+            feeds: vec![
+                feed("https://a.example/feed?x=1&y=2", Some("News")),
+                feed("https://b.example/feed", None),
+            ],
+        };
+        let parsed = Opml::parse(&opml.to_xml()).unwrap();
+        assert_eq!(parsed.title, opml.title);
+        assert_eq!(parsed.feeds[0], opml.feeds[0]);
+        assert_eq!(parsed.feeds[1].category.as_deref(), Some("All"));
+    }
+
+    #[test]
+    fn test_diff() {
+        let current = Opml {
+            title: String::new(),
+            feeds: vec![
+                feed("https://keep.example", Some("A")),
+                feed("https://move.example", Some("A")),
+                feed("https://drop.example", Some("A")),
+            ],
+        };
+        let desired = Opml {
+            title: String::new(),
+            feeds: vec![
+                feed("https://keep.example", Some("A")),
+                feed("https://move.example", Some("B")),
+                feed("https://new.example", Some("B")),
+            ],
+        };
+        let diff = current.diff(&desired);
+        assert_eq!(diff.added, vec![feed("https://new.example", Some("B"))]);
+        assert_eq!(diff.removed, vec![feed("https://drop.example", Some("A"))]);
+        assert_eq!(diff.moved, vec![feed("https://move.example", Some("B"))]);
+        assert!(current.diff(&current).is_empty());
+    }
+
+    #[test]
+    fn test_diff_default_category() {
+        let current = Opml {
+            title: String::new(),
+            feeds: vec![
+                feed("https://loose.example", Some("All")),
+                feed("https://case.example", Some("Tech")),
+            ],
+        };
+        let desired = Opml {
+            title: String::new(),
+            feeds: vec![
+                feed("https://loose.example", None),
+                feed("https://case.example", Some("tech")),
+            ],
+        };
+        assert!(current.diff(&desired).is_empty());
+    }
+
+    #[test]
+    fn test_diff_export_against_itself() {
+        let desired = Opml::parse(DOCUMENT).unwrap();
+        let export = Opml::parse(&desired.to_xml()).unwrap();
+        assert!(export.diff(&desired).is_empty());
+        assert!(desired.diff(&export).is_empty());
+    }
+}
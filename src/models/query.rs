@@ -0,0 +1,301 @@
+use chrono::{DateTime, Utc};
+
+use super::EntryStatus;
+
+pub const DEFAULT_PAGE_SIZE: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryOrder {
+    Id,
+    Status,
+    PublishedAt,
+    CategoryTitle,
+    CategoryId,
+}
+
+impl EntryOrder {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EntryOrder::Id => "id",
+            EntryOrder::Status => "status",
+            EntryOrder::PublishedAt => "published_at",
+            EntryOrder::CategoryTitle => "category_title",
+            EntryOrder::CategoryId => "category_id",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Asc,
+    Desc,
+}
+
+impl Direction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Direction::Asc => "asc",
+            Direction::Desc => "desc",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EntryQuery {
+    pub status: Vec<EntryStatus>,
+    pub starred: Option<bool>,
+    pub search: Option<String>,
+    pub category_id: Option<i64>,
+    pub feed_id: Option<i64>,
+    pub before: Option<i64>,
+    pub after: Option<i64>,
+    pub published_before: Option<i64>,
+    pub published_after: Option<i64>,
+    pub changed_before: Option<i64>,
+    pub changed_after: Option<i64>,
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+    pub after_entry_id: Option<i64>,
+    pub before_entry_id: Option<i64>,
+    pub order: Option<EntryOrder>,
+    pub direction: Option<Direction>,
+}
+
+impl EntryQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn unread() -> Self {
+        Self::new().status(EntryStatus::Unread)
+    }
+
+    // Can be called several times, Miniflux ORs the statuses
+    pub fn status(mut self, status: EntryStatus) -> Self {
+        if !self.status.contains(&status) {
+            self.status.push(status);
+        }
+        self
+    }
+
+    pub fn starred(mut self, starred: bool) -> Self {
+        self.starred = Some(starred);
+        self
+    }
+
+    pub fn search(mut self, search: &str) -> Self {
+        self.search = Some(search.to_string());
+        self
+    }
+
+    pub fn category_id(mut self, category_id: i64) -> Self {
+        self.category_id = Some(category_id);
+        self
+    }
+
+    pub fn feed_id(mut self, feed_id: i64) -> Self {
+        self.feed_id = Some(feed_id);
+        self
+    }
+
+    pub fn before(mut self, date: DateTime<Utc>) -> Self {
+        self.before = Some(date.timestamp());
+        self
+    }
+
+    pub fn after(mut self, date: DateTime<Utc>) -> Self {
+        self.after = Some(date.timestamp());
+        self
+    }
+
+    pub fn published_before(mut self, date: DateTime<Utc>) -> Self {
+        self.published_before = Some(date.timestamp());
+        self
+    }
+
+    pub fn published_after(mut self, date: DateTime<Utc>) -> Self {
+        self.published_after = Some(date.timestamp());
+        self
+    }
+
+    pub fn changed_before(mut self, date: DateTime<Utc>) -> Self {
+        self.changed_before = Some(date.timestamp());
+        self
+    }
+
+    pub fn changed_after(mut self, date: DateTime<Utc>) -> Self {
+        self.changed_after = Some(date.timestamp());
+        self
+    }
+
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn offset(mut self, offset: usize) -> Self {
+        self.offset = Some(offset);
+        self
+    }
+
+    pub fn after_entry_id(mut self, entry_id: i64) -> Self {
+        self.after_entry_id = Some(entry_id);
+        self
+    }
+
+    pub fn before_entry_id(mut self, entry_id: i64) -> Self {
+        self.before_entry_id = Some(entry_id);
+        self
+    }
+
+    pub fn order(mut self, order: EntryOrder) -> Self {
+        self.order = Some(order);
+        self
+    }
+
+    pub fn direction(mut self, direction: Direction) -> Self {
+        self.direction = Some(direction);
+        self
+    }
+
+    pub fn page_size(&self) -> usize {
+        self.limit.unwrap_or(DEFAULT_PAGE_SIZE)
+    }
+
+    // Walking by `after_entry_id` is only stable when entries are sorted by id
+    pub fn uses_cursor(&self) -> bool {
+        self.order.is_none_or(|order| order == EntryOrder::Id)
+    }
+
+    pub fn to_query(&self) -> Vec<(&'static str, String)> {
+        let mut query = Vec::new();
+        for status in self.status.iter() {
+            query.push(("status", status.as_str().to_string()));
+        }
+        if let Some(starred) = self.starred {
+            query.push(("starred", starred.to_string()));
+        }
+        if let Some(search) = &self.search {
+            query.push(("search", search.clone()));
+        }
+        let numbers = [
+            ("category_id", self.category_id),
+            ("feed_id", self.feed_id),
+            ("before", self.before),
+            ("after", self.after),
+            ("published_before", self.published_before),
+            ("published_after", self.published_after),
+            ("changed_before", self.changed_before),
+            ("changed_after", self.changed_after),
+        ];
+        for (name, value) in numbers {
+            if let Some(value) = value {
+                query.push((name, value.to_string()));
+            }
+        }
+        if let Some(limit) = self.limit {
+            query.push(("limit", limit.to_string()));
+        }
+        if let Some(offset) = self.offset {
+            query.push(("offset", offset.to_string()));
+        }
+        if let Some(after_entry_id) = self.after_entry_id {
+            query.push(("after_entry_id", after_entry_id.to_string()));
+        }
+        if let Some(before_entry_id) = self.before_entry_id {
+            query.push(("before_entry_id", before_entry_id.to_string()));
+        }
+        if let Some(order) = self.order {
+            query.push(("order", order.as_str().to_string()));
+        }
+        if let Some(direction) = self.direction {
+            query.push(("direction", direction.as_str().to_string()));
+        }
+        query
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Direction, EntryOrder, EntryQuery, DEFAULT_PAGE_SIZE};
+    use crate::models::EntryStatus;
+    use chrono::{TimeZone, Utc};
+
+    #[test]
+    fn test_entry_query_default_is_empty() {
+        let query = EntryQuery::new();
+        assert!(query.to_query().is_empty());
+        assert_eq!(query.page_size(), DEFAULT_PAGE_SIZE);
+        assert!(query.uses_cursor());
+    }
+
+    #[test]
+    fn test_entry_query_to_query() {
+        let query = EntryQuery::unread()
+            .limit(10)
+            .offset(20)
+            .order(EntryOrder::PublishedAt)
+            .direction(Direction::Asc);
+        assert_eq!(
+            query.to_query(),
+            vec![
+                ("status", "unread".to_string()),
+                ("limit", "10".to_string()),
+                ("offset", "20".to_string()),
+                ("order", "published_at".to_string()),
+                ("direction", "asc".to_string()),
+            ]
+        );
+        assert!(!query.uses_cursor());
+    }
+
+    #[test]
+    fn test_entry_query_cursor() {
+        let query = EntryQuery::new().order(EntryOrder::Id).after_entry_id(42);
+        assert!(query.uses_cursor());
+        assert_eq!(
+            query.to_query(),
+            vec![
+                ("after_entry_id", "42".to_string()),
+                ("order", "id".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_entry_query_multiple_status() {
+        let query = EntryQuery::unread()
+            .status(EntryStatus::Read)
+            .status(EntryStatus::Unread);
+        assert_eq!(
+            query.to_query(),
+            vec![
+                ("status", "unread".to_string()),
+                ("status", "read".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_entry_query_filters() {
+        let date = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let query = EntryQuery::new()
+            .starred(true)
+            .search("rust")
+            .category_id(3)
+            .feed_id(7)
+            .published_after(date)
+            .changed_after(date);
+        assert_eq!(
+            query.to_query(),
+            vec![
+                ("starred", "true".to_string()),
+                ("search", "rust".to_string()),
+                ("category_id", "3".to_string()),
+                ("feed_id", "7".to_string()),
+                ("published_after", "1704067200".to_string()),
+                ("changed_after", "1704067200".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,354 @@
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use reqwest::{header::RETRY_AFTER, RequestBuilder, Response, StatusCode};
+use serde_json::Value;
+use std::{env, future::Future, time::Duration};
+use tracing::{debug, warn};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    // Total tries, including the first one
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    // Fraction of the delay randomly added or removed, between 0 and 1
+    pub jitter: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            jitter: 0.2,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn none() -> Self {
+        RetryPolicy {
+            max_attempts: 1,
+            ..Self::default()
+        }
+    }
+
+    pub fn from_env() -> Self {
+        let default = Self::default();
+        let millis = |name: &str, default: Duration| {
+            env::var(name)
+                .ok()
+                .and_then(|value| value.parse::<u64>().ok())
+                .map(Duration::from_millis)
+                .unwrap_or(default)
+        };
+        RetryPolicy {
+            max_attempts: env::var("RETRY_MAX_ATTEMPTS")
+                .ok()
+                .and_then(|value| value.parse::<u32>().ok())
+                .unwrap_or(default.max_attempts)
+                .max(1),
+            base_delay: millis("RETRY_BASE_DELAY_MS", default.base_delay),
+            max_delay: millis("RETRY_MAX_DELAY_MS", default.max_delay),
+            jitter: env::var("RETRY_JITTER")
+                .ok()
+                .and_then(|value| value.parse::<f64>().ok())
+                .unwrap_or(default.jitter)
+                .clamp(0.0, 1.0),
+        }
+    }
+
+    // Exponential delay before the given retry (1 for the first retry)
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let factor = 2u32.saturating_pow(retry.saturating_sub(1));
+        let delay = self.base_delay.saturating_mul(factor).min(self.max_delay);
+        if self.jitter <= 0.0 {
+            return delay;
+        }
+        let spread = rand::rng().random_range(-self.jitter..=self.jitter);
+        delay.mul_f64(1.0 + spread)
+    }
+
+    pub async fn send(&self, request: RequestBuilder) -> Result<Response, reqwest::Error> {
+        let mut attempt = 1;
+        loop {
+            // Streaming bodies cannot be replayed, those get a single try
+            let Some(current) = request.try_clone() else {
+                return request.send().await;
+            };
+            let last = attempt >= self.max_attempts;
+            let (client, current) = current.build_split();
+            let current = current?;
+            // A timed out POST, or one a gateway failed on, may have been
+            // processed already, resending it would duplicate messages,
+            // resources or paid completions
+            let idempotent = current.method().is_idempotent();
+            let delay = match client.execute(current).await {
+                Ok(response) if !last && is_retryable(response.status()) && (idempotent || is_rejected(&response)) => {
+                    let status = response.status();
+                    let hint = retry_after(response).await;
+                    warn!("Request failed with {}, retry {} of {}", status, attempt, self.max_attempts - 1);
+                    // Never wait longer than configured, whatever the server asks
+                    hint.map(|hint| hint.min(self.max_delay))
+                        .unwrap_or_else(|| self.backoff(attempt))
+                }
+                Err(e) if !last && (e.is_connect() || (idempotent && e.is_timeout())) => {
+                    warn!("Request failed: {}, retry {} of {}", e, attempt, self.max_attempts - 1);
+                    self.backoff(attempt)
+                }
+                result => return result,
+            };
+            debug!("Waiting {:?} before retrying", delay);
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
+
+// Statuses worth trying again, shared with `MinifluxError::is_retryable`
+pub fn is_retryable(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS
+        || status == StatusCode::REQUEST_TIMEOUT
+        || (status.is_server_error() && status != StatusCode::NOT_IMPLEMENTED)
+}
+
+// Answers that guarantee the request was not processed, the only ones a
+// non idempotent request is sent again after
+fn is_rejected(response: &Response) -> bool {
+    match response.status() {
+        StatusCode::TOO_MANY_REQUESTS => true,
+        StatusCode::SERVICE_UNAVAILABLE => response.headers().contains_key(RETRY_AFTER),
+        _ => false,
+    }
+}
+
+// Delay asked by the server, from the `Retry-After` header, Matrix's
+// `retry_after_ms` or Telegram's `parameters.retry_after`
+async fn retry_after(response: Response) -> Option<Duration> {
+    let header = response
+        .headers()
+        .get(RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(parse_retry_after);
+    if header.is_some() {
+        return header;
+    }
+    let body: Value = response.json().await.ok()?;
+    if let Some(millis) = body.get("retry_after_ms").and_then(Value::as_u64) {
+        return Some(Duration::from_millis(millis));
+    }
+    body.pointer("/parameters/retry_after")
+        .and_then(Value::as_u64)
+        .map(Duration::from_secs)
+}
+
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = DateTime::parse_from_rfc2822(value).ok()?.with_timezone(&Utc);
+    Some((date - Utc::now()).to_std().unwrap_or_default())
+}
+
+pub trait RetryRequest {
+    fn send_with(self, policy: &RetryPolicy) -> impl Future<Output = Result<Response, reqwest::Error>>;
+}
+
+impl RetryRequest for RequestBuilder {
+    fn send_with(self, policy: &RetryPolicy) -> impl Future<Output = Result<Response, reqwest::Error>> {
+        policy.send(self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{is_retryable, parse_retry_after, RetryPolicy, RetryRequest};
+    use reqwest::{Client, StatusCode};
+    use std::time::Duration;
+
+    fn fast() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(5),
+            jitter: 0.0,
+        }
+    }
+
+    #[test]
+    fn test_backoff() {
+        let policy = RetryPolicy {
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(350),
+            jitter: 0.0,
+            ..RetryPolicy::default()
+        };
+        assert_eq!(policy.backoff(1), Duration::from_millis(100));
+        assert_eq!(policy.backoff(2), Duration::from_millis(200));
+        assert_eq!(policy.backoff(3), Duration::from_millis(350));
+        assert_eq!(policy.backoff(40), Duration::from_millis(350));
+        let jittered = RetryPolicy { jitter: 0.5, ..policy };
+        for _ in 0..20 {
+            let delay = jittered.backoff(1);
+            assert!(delay >= Duration::from_millis(50) && delay <= Duration::from_millis(150));
+        }
+    }
+
+    #[test]
+    fn test_is_retryable() {
+        assert!(is_retryable(StatusCode::BAD_GATEWAY));
+        assert!(is_retryable(StatusCode::TOO_MANY_REQUESTS));
+        assert!(is_retryable(StatusCode::REQUEST_TIMEOUT));
+        assert!(!is_retryable(StatusCode::NOT_IMPLEMENTED));
+        assert!(!is_retryable(StatusCode::UNAUTHORIZED));
+        assert!(!is_retryable(StatusCode::NOT_FOUND));
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        assert_eq!(parse_retry_after("3"), Some(Duration::from_secs(3)));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), Some(Duration::ZERO));
+        assert_eq!(parse_retry_after("soon"), None);
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_max_attempts() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server.mock("GET", "/")
+            .with_status(503)
+            .expect(3)
+            .create_async()
+            .await;
+
+        let response = Client::new().get(server.url()).send_with(&fast()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_does_not_retry_client_errors() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server.mock("GET", "/")
+            .with_status(404)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let response = Client::new().get(server.url()).send_with(&fast()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_retry_after_is_capped_by_max_delay() {
+        let mut server = mockito::Server::new_async().await;
+        let limited = server.mock("GET", "/")
+            .with_status(429)
+            .with_header("retry-after", "86400")
+            .expect(1)
+            .create_async()
+            .await;
+        let ok = server.mock("GET", "/")
+            .with_status(200)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let response = tokio::time::timeout(
+            Duration::from_secs(5),
+            Client::new().get(server.url()).send_with(&fast()),
+        )
+        .await
+        .expect("the Retry-After hint was not capped")
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        limited.assert_async().await;
+        ok.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_timeouts_are_retried_only_for_idempotent_methods() {
+        // Accepts connections but never answers
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let accepted = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = accepted.clone();
+        tokio::spawn(async move {
+            let mut sockets = Vec::new();
+            while let Ok((socket, _)) = listener.accept().await {
+                counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                sockets.push(socket);
+            }
+        });
+        let client = Client::builder().timeout(Duration::from_millis(100)).build().unwrap();
+
+        assert!(client.post(&url).body("hello").send_with(&fast()).await.unwrap_err().is_timeout());
+        assert_eq!(accepted.load(std::sync::atomic::Ordering::SeqCst), 1);
+        assert!(client.get(&url).send_with(&fast()).await.unwrap_err().is_timeout());
+        assert_eq!(accepted.load(std::sync::atomic::Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn test_gateway_errors_are_retried_only_for_idempotent_methods() {
+        let mut server = mockito::Server::new_async().await;
+        let post = server.mock("POST", "/")
+            .with_status(502)
+            .expect(1)
+            .create_async()
+            .await;
+        let put = server.mock("PUT", "/")
+            .with_status(502)
+            .expect(3)
+            .create_async()
+            .await;
+
+        let response = Client::new().post(server.url()).body("hello").send_with(&fast()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+        Client::new().put(server.url()).body("hello").send_with(&fast()).await.unwrap();
+        post.assert_async().await;
+        put.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_rejected_posts_are_retried() {
+        let mut server = mockito::Server::new_async().await;
+        let limited = server.mock("POST", "/")
+            .with_status(429)
+            .expect(1)
+            .create_async()
+            .await;
+        let unavailable = server.mock("POST", "/")
+            .with_status(503)
+            .with_header("retry-after", "0")
+            .expect(1)
+            .create_async()
+            .await;
+        let ok = server.mock("POST", "/")
+            .with_status(200)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let response = Client::new().post(server.url()).body("hello").send_with(&fast()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        limited.assert_async().await;
+        unavailable.assert_async().await;
+        ok.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_unavailable_post_without_hint_is_not_retried() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server.mock("POST", "/")
+            .with_status(503)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let response = Client::new().post(server.url()).body("hello").send_with(&fast()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        mock.assert_async().await;
+    }
+}
@@ -0,0 +1,143 @@
+use std::{
+    fs, io,
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+use tracing::{debug, warn};
+
+use super::Digest;
+
+const MAX_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+// Keeps the model answer for each entry, so an entry already summarized with
+// the same content, model and prompt never costs a second call
+#[derive(Debug, Clone)]
+pub struct SummaryCache {
+    dir: PathBuf,
+    pub max_age: Duration,
+}
+
+impl SummaryCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        SummaryCache {
+            dir: dir.into(),
+            max_age: MAX_AGE,
+        }
+    }
+
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = max_age;
+        self
+    }
+
+    // `model` identifies who summarized, changing the model or the prompt
+    // invalidates every summary
+    fn path(&self, entry_id: i64, model: &str, content: &str) -> PathBuf {
+        self.dir.join(format!(
+            "{}-{:016x}-{:016x}.json",
+            entry_id,
+            fnv1a(model.as_bytes()),
+            fnv1a(content.as_bytes())
+        ))
+    }
+
+    pub fn get(&self, entry_id: i64, model: &str, content: &str) -> Option<Digest> {
+        let data = fs::read(self.path(entry_id, model, content)).ok()?;
+        serde_json::from_slice(&data).ok()
+    }
+
+    pub fn put(&self, entry_id: i64, model: &str, content: &str, digest: &Digest) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        fs::write(self.path(entry_id, model, content), serde_json::to_vec(digest)?)?;
+        debug!("Summary of entry {} cached", entry_id);
+        Ok(())
+    }
+
+    // Removes summaries older than `max_age`, entries are long read by then
+    pub fn prune(&self) -> io::Result<usize> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e),
+        };
+        let now = SystemTime::now();
+        let mut removed = 0;
+        for entry in entries {
+            let entry = entry?;
+            let path = entry.path();
+            if path.extension().is_none_or(|extension| extension != "json") {
+                continue;
+            }
+            let age = entry
+                .metadata()
+                .and_then(|metadata| metadata.modified())
+                .map(|modified| now.duration_since(modified).unwrap_or_default());
+            match age {
+                Ok(age) if age > self.max_age => {
+                    fs::remove_file(&path)?;
+                    removed += 1;
+                }
+                Ok(_) => {}
+                Err(e) => warn!("Unable to check the age of {:?}: {}", path, e),
+            }
+        }
+        debug!("{} cached summaries pruned", removed);
+        Ok(removed)
+    }
+}
+
+// Stable across builds and platforms, unlike the std hasher
+fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{fnv1a, SummaryCache};
+    use crate::models::{Digest, DigestItem};
+
+    #[test]
+    fn test_fnv1a() {
+        assert_eq!(fnv1a(b""), 0xcbf29ce484222325);
+        assert_eq!(fnv1a(b"a"), 0xaf63dc4c8601ec8c);
+    }
+
+    #[test]
+    fn test_summary_cache_roundtrip() {
+        let dir = std::env::temp_dir().join(format!("miniflux-summaries-{}", std::process::id()));
+        let cache = SummaryCache::new(&dir);
+        let digest = Digest {
+            news: vec![DigestItem {
+                title: "One".to_string(),
+                url: "https://example.org/1".to_string(),
+                summary: "First".to_string(),
+            }],
+            ..Digest::default()
+        };
+        assert!(cache.get(1, "gpt", "content").is_none());
+        cache.put(1, "gpt", "content", &digest).unwrap();
+        assert_eq!(cache.get(1, "gpt", "content"), Some(digest));
+        // Edited entries are summarized again
+        assert!(cache.get(1, "gpt", "new content").is_none());
+        assert!(cache.get(2, "gpt", "content").is_none());
+        // And so are entries summarized by another model or prompt
+        assert!(cache.get(1, "llama", "content").is_none());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_summary_cache_prune() {
+        let dir = std::env::temp_dir().join(format!("miniflux-summaries-prune-{}", std::process::id()));
+        let cache = SummaryCache::new(&dir);
+        assert_eq!(cache.prune().unwrap(), 0);
+        cache.put(1, "gpt", "content", &Digest::default()).unwrap();
+        assert_eq!(cache.prune().unwrap(), 0);
+        let expired = cache.with_max_age(std::time::Duration::ZERO);
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        assert_eq!(expired.prune().unwrap(), 1);
+        assert!(expired.get(1, "gpt", "content").is_none());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
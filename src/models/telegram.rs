@@ -2,6 +2,9 @@ use reqwest::Client;
 use serde::{Serialize, Deserialize};
 use tracing::debug;
 
+use super::{Capabilities, CustomError, Markup, Notifier, RetryPolicy, RetryRequest};
+use async_trait::async_trait;
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct TelegramClient{
     token: String,
@@ -10,6 +13,10 @@ pub struct TelegramClient{
     thread_id: String,
     #[serde(skip)]
     pub base_url: Option<String>,
+    #[serde(skip)]
+    client: Client,
+    #[serde(skip)]
+    retry: RetryPolicy,
 }
 
 #[derive(Serialize)]
@@ -33,6 +40,8 @@ impl TelegramClient{
             chat_id,
             thread_id,
             base_url: None,
+            client: Client::new(),
+            retry: RetryPolicy::default(),
         }
     }
 
@@ -42,13 +51,33 @@ impl TelegramClient{
             chat_id,
             thread_id,
             base_url: Some(base_url),
+            client: Client::new(),
+            retry: RetryPolicy::default(),
         }
     }
 
+    pub fn with_client(mut self, client: Client) -> Self{
+        self.client = client;
+        self
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self{
+        self.retry = retry;
+        self
+    }
+
     fn get_base_url(&self) -> &str {
         self.base_url.as_deref().unwrap_or(URL)
     }
 
+    // Telegram answers with `{"ok":true,...}` once the message is accepted
+    pub fn is_sent(response: &str) -> bool {
+        serde_json::from_str::<serde_json::Value>(response)
+            .ok()
+            .and_then(|value| value.get("ok").and_then(|ok| ok.as_bool()))
+            .unwrap_or(false)
+    }
+
     pub async fn send_message(&self, message: &str) -> Result<String, reqwest::Error>{
         debug!("Sending Telegram message: {}", message);
         let url = format!("{}/bot{}/sendMessage", self.get_base_url(), self.token);
@@ -58,10 +87,10 @@ impl TelegramClient{
             text: message.into(),
             parse_mode: "MarkdownV2".into(),
         };
-        let response = Client::new()
+        let response = self.client
             .post(&url)
             .json(&payload)
-            .send()
+            .send_with(&self.retry)
             .await?;
         
         let status = response.status();
@@ -77,13 +106,35 @@ impl TelegramClient{
     }
 }
 
+#[async_trait]
+impl Notifier for TelegramClient {
+    fn name(&self) -> &str {
+        "Telegram"
+    }
 
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            max_length: 4096,
+            markup: Markup::MarkdownV2,
+            icons: false,
+        }
+    }
+
+    async fn send(&self, message: &str) -> Result<(), CustomError> {
+        let response = self.send_message(message).await?;
+        debug!("Telegram response: {:?}", response);
+        match Self::is_sent(&response) {
+            true => Ok(()),
+            false => Err(format!("Telegram rejected the message: {}", response).into()),
+        }
+    }
+}
 
 #[cfg(test)]
 mod test{
-    use super::TelegramClient;
+    use super::{Notifier, RetryPolicy, TelegramClient};
     use dotenv::dotenv;
-    use std::{env, str::FromStr};
+    use std::{env, str::FromStr, time::Duration};
     use tracing_subscriber::{
         EnvFilter,
         layer::SubscriberExt,
@@ -195,6 +246,7 @@ mod test{
         assert!(result.is_ok());
         let body = result.unwrap();
         assert!(body.contains("\"ok\":true"));
+        assert!(TelegramClient::is_sent(&body));
     }
 
     #[tokio::test]
@@ -218,7 +270,62 @@ mod test{
         assert!(result.is_ok());
         let body = result.unwrap();
         assert!(body.contains("\"ok\":false"));
+        assert!(!TelegramClient::is_sent(&body));
     }
-}
 
+    #[tokio::test]
+    async fn test_send_message_retries_after_flood_limit() {
+        let mut server = mockito::Server::new_async().await;
+        let limited = server.mock("POST", "/bottest_token/sendMessage")
+            .with_status(429)
+            .with_body(r#"{"ok":false,"error_code":429,"description":"Too Many Requests: retry after 0","parameters":{"retry_after":0}}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let sent = server.mock("POST", "/bottest_token/sendMessage")
+            .with_status(200)
+            .with_body(r#"{"ok":true,"result":{"message_id":1}}"#)
+            .expect(1)
+            .create_async()
+            .await;
 
+        let client = TelegramClient::with_base_url(
+            "test_token".to_string(),
+            "123456".to_string(),
+            "0".to_string(),
+            server.url(),
+        )
+        .with_retry(RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(5),
+            jitter: 0.0,
+        });
+
+        let body = client.send_message("Test message").await.unwrap();
+        assert!(body.contains("\"ok\":true"));
+        limited.assert_async().await;
+        sent.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_notifier_fails_when_telegram_rejects() {
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server.mock("POST", "/bottest_token/sendMessage")
+            .with_status(400)
+            .with_body(r#"{"ok":false,"error_code":400,"description":"Bad Request: can't parse entities"}"#)
+            .create_async()
+            .await;
+
+        let client = TelegramClient::with_base_url(
+            "test_token".to_string(),
+            "123456".to_string(),
+            "0".to_string(),
+            server.url(),
+        )
+        .with_retry(RetryPolicy::none());
+
+        let result = client.send("Test message").await;
+        assert!(result.unwrap_err().to_string().contains("can't parse entities"));
+    }
+}
@@ -0,0 +1,87 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct User {
+    pub id: i64,
+    #[serde(default)]
+    pub username: String,
+    #[serde(default)]
+    pub is_admin: bool,
+    #[serde(default)]
+    pub theme: String,
+    #[serde(default)]
+    pub language: String,
+    #[serde(default)]
+    pub timezone: String,
+    #[serde(default)]
+    pub entry_sorting_direction: String,
+    #[serde(default)]
+    pub entries_per_page: i64,
+    #[serde(default)]
+    pub last_login_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UserCreationRequest {
+    pub username: String,
+    pub password: String,
+    pub is_admin: bool,
+}
+
+impl UserCreationRequest {
+    pub fn new(username: &str, password: &str) -> Self {
+        UserCreationRequest {
+            username: username.to_string(),
+            password: password.to_string(),
+            is_admin: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct UserModificationRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_admin: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub theme: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entry_sorting_direction: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entries_per_page: Option<i64>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::{User, UserCreationRequest, UserModificationRequest};
+
+    #[test]
+    fn test_user_deserialize() {
+        let json = r#"{"id":1,"username":"admin","is_admin":true,"last_login_at":null}"#;
+        let user: User = serde_json::from_str(json).unwrap();
+        assert_eq!(user.username, "admin");
+        assert!(user.is_admin);
+        assert!(user.last_login_at.is_none());
+    }
+
+    #[test]
+    fn test_user_requests_serialize() {
+        let request = UserCreationRequest::new("bob", "secret");
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            serde_json::json!({"username": "bob", "password": "secret", "is_admin": false})
+        );
+        let changes = UserModificationRequest {
+            theme: Some("dark_serif".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(serde_json::to_string(&changes).unwrap(), r#"{"theme":"dark_serif"}"#);
+    }
+}
@@ -0,0 +1,19 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct VersionInfo {
+    #[serde(default)]
+    pub version: String,
+    #[serde(default)]
+    pub commit: String,
+    #[serde(default)]
+    pub build_date: String,
+    #[serde(default)]
+    pub go_version: String,
+    #[serde(default)]
+    pub compiler: String,
+    #[serde(default)]
+    pub arch: String,
+    #[serde(default)]
+    pub os: String,
+}
@@ -0,0 +1,1022 @@
+use async_trait::async_trait;
+use futures::{stream, StreamExt};
+use serde_json::{json, Value};
+use std::collections::{hash_map, HashMap};
+use tracing::{debug, error, info, warn};
+
+pub use crate::models::Acknowledger;
+use crate::models::{
+    estimate_tokens, truncate, AckPolicy, ContentFetcher, CustomError, DeliveryTracker, Digest, DigestItem, Direction,
+    Entry, EntryOrder, EntryQuery, Icon, IconCache, MinifluxClient, Model, Normalizer, Notification, Notifier,
+    SummaryCache, CHARS_PER_TOKEN,
+};
+
+// Every stage of the digest can be swapped by implementing its trait
+
+#[async_trait]
+pub trait Fetcher: Send + Sync {
+    async fn fetch(&self, limit: usize) -> Result<Vec<Entry>, CustomError>;
+}
+
+#[async_trait]
+pub trait Enricher: Send + Sync {
+    async fn enrich(&self, entries: Vec<Entry>) -> Vec<Entry>;
+}
+
+#[async_trait]
+pub trait Summarizer: Send + Sync {
+    async fn summarize(&self, news: &[Value]) -> Result<Digest, CustomError>;
+
+    // Tokens every call spends besides the news, like the prompt
+    fn overhead(&self) -> usize {
+        0
+    }
+
+    // Identifies the model and prompt, answers cached under another
+    // fingerprint are stale
+    fn fingerprint(&self) -> String {
+        String::new()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RunReport {
+    pub fetched: usize,
+    pub attempted: usize,
+    pub delivered: usize,
+    pub acknowledged: usize,
+}
+
+pub struct Pipeline {
+    fetcher: Box<dyn Fetcher>,
+    enrichers: Vec<Box<dyn Enricher>>,
+    summarizer: Box<dyn Summarizer>,
+    notifiers: Vec<Box<dyn Notifier>>,
+    acknowledger: Box<dyn Acknowledger>,
+    delivery: DeliveryTracker,
+    max_entries: usize,
+    per_item: bool,
+    attach_media: bool,
+    icons: Option<(IconCache, MinifluxClient)>,
+}
+
+impl Pipeline {
+    pub fn new(
+        fetcher: impl Fetcher + 'static,
+        summarizer: impl Summarizer + 'static,
+        acknowledger: impl Acknowledger + 'static,
+    ) -> Self {
+        Pipeline {
+            fetcher: Box::new(fetcher),
+            enrichers: Vec::new(),
+            summarizer: Box::new(summarizer),
+            notifiers: Vec::new(),
+            acknowledger: Box::new(acknowledger),
+            delivery: DeliveryTracker::default(),
+            max_entries: 10,
+            per_item: false,
+            attach_media: true,
+            icons: None,
+        }
+    }
+
+    // Enrichers run in the order they are added
+    pub fn with_enricher(mut self, enricher: impl Enricher + 'static) -> Self {
+        self.enrichers.push(Box::new(enricher));
+        self
+    }
+
+    pub fn with_notifier(mut self, notifier: impl Notifier + 'static) -> Self {
+        self.notifiers.push(Box::new(notifier));
+        self
+    }
+
+    // Sends one message per news item instead of a single digest
+    pub fn with_per_item(mut self, per_item: bool) -> Self {
+        self.per_item = per_item;
+        self
+    }
+
+    // Adds lead image and podcast links to the news items
+    pub fn with_attach_media(mut self, attach_media: bool) -> Self {
+        self.attach_media = attach_media;
+        self
+    }
+
+    // Feed icons for the notifiers that can show them
+    pub fn with_icons(mut self, cache: IconCache, miniflux: MinifluxClient) -> Self {
+        self.icons = Some((cache, miniflux));
+        self
+    }
+
+    pub fn with_ack_policy(mut self, policy: AckPolicy) -> Self {
+        self.delivery.policy = policy;
+        self
+    }
+
+    pub fn with_max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries;
+        self
+    }
+
+    pub fn pending(&self) -> Vec<i64> {
+        self.delivery.pending()
+    }
+
+    pub async fn run_once(&mut self) -> Result<RunReport, CustomError> {
+        // Without notifiers every entry would be marked as read unseen
+        if self.notifiers.is_empty() {
+            return Err("No notifier configured, add one with with_notifier".into());
+        }
+        let mut report = RunReport::default();
+        self.acknowledge().await;
+        // Pending entries are still unread, fetch enough to get past them
+        let limit = self.max_entries + self.delivery.pending().len();
+        let entries = self.fetcher.fetch(limit).await?;
+        // Already delivered, only waiting to be marked as read
+        let mut entries: Vec<Entry> = entries
+            .into_iter()
+            .filter(|entry| !self.delivery.is_pending(entry.id))
+            .take(self.max_entries)
+            .collect();
+        for enricher in self.enrichers.iter() {
+            entries = enricher.enrich(entries).await;
+        }
+        report.fetched = entries.len();
+        if entries.is_empty() {
+            info!("No new entries");
+            return Ok(report);
+        }
+        let news = entries.iter().map(news_item).collect::<Vec<_>>();
+        let digest = self.summarizer.summarize(&news).await?;
+        debug!("Digest: {:?}", digest);
+        let notifications = self.notifications(&digest.news, &entries).await;
+        for notifier in self.notifiers.iter() {
+            report.attempted += 1;
+            let result = if self.per_item {
+                send_items(notifier.as_ref(), &notifications).await
+            } else {
+                notifier.send_digest(&notifications).await
+            };
+            match result {
+                Ok(()) => {
+                    debug!("News delivered to {}", notifier.name());
+                    report.delivered += 1;
+                }
+                Err(e) => error!("Error sending news to {}: {}", notifier.name(), e),
+            }
+        }
+        if self.delivery.policy.is_confirmed(report.delivered, report.attempted) {
+            // Entries the summarizer failed on were never delivered
+            let delivered = entries
+                .iter()
+                .map(|entry| entry.id)
+                .filter(|id| !digest.failed.contains(id))
+                .collect::<Vec<_>>();
+            report.acknowledged = delivered.len();
+            self.delivery.queue(delivered);
+            self.acknowledge().await;
+        } else {
+            error!("News not delivered, entries stay unread for the next cycle");
+        }
+        Ok(report)
+    }
+
+    async fn notifications(&self, items: &[DigestItem], entries: &[Entry]) -> Vec<Notification> {
+        let wants_icons = self.notifiers.iter().any(|notifier| notifier.capabilities().icons);
+        let mut icons: HashMap<i64, Option<Icon>> = HashMap::new();
+        let mut notifications = Vec::new();
+        for item in items {
+            let entry = find_entry(entries, &item.url);
+            let mut notification = Notification {
+                title: item.title.clone(),
+                url: item.url.clone(),
+                summary: item.summary.clone(),
+                links: media_links(entry, self.attach_media),
+                icon: None,
+            };
+            let icon_id = entry
+                .and_then(|entry| entry.feed.as_ref())
+                .and_then(|feed| feed.icon.as_ref())
+                .map(|icon| icon.icon_id);
+            if let (Some((cache, miniflux)), Some(icon_id), true) = (&self.icons, icon_id, wants_icons) {
+                if let hash_map::Entry::Vacant(slot) = icons.entry(icon_id) {
+                    slot.insert(match miniflux.cached_icon(cache, icon_id).await {
+                        Ok(icon) => Some(icon),
+                        Err(e) => {
+                            error!("Error getting icon {}: {}", icon_id, e);
+                            None
+                        }
+                    });
+                }
+                notification.icon = icons.get(&icon_id).cloned().flatten();
+            }
+            notifications.push(notification);
+        }
+        notifications
+    }
+
+    async fn acknowledge(&mut self) {
+        if let Err(e) = self.delivery.flush(self.acknowledger.as_ref()).await {
+            error!("Error marking delivered entries as read, will retry: {}", e);
+        }
+    }
+}
+
+fn news_item(entry: &Entry) -> Value {
+    let feed_title = entry
+        .feed
+        .as_ref()
+        .map(|feed| feed.title.as_str())
+        .unwrap_or("No feed title");
+    json!({
+        "id": entry.id,
+        "url": or_default(&entry.url, "No URL"),
+        "title": or_default(&entry.title, "No title"),
+        "feed_title": or_default(feed_title, "No feed title"),
+        "published_at": or_default(&entry.published_at, "No published_at"),
+        "author": or_default(&entry.author, "No author"),
+        "resume": or_default(&entry.content, "No content"),
+    })
+}
+
+fn or_default<'a>(value: &'a str, default: &'a str) -> &'a str {
+    if value.is_empty() {
+        default
+    } else {
+        value
+    }
+}
+
+// Unread entries, either globally or from a list of categories
+pub struct MinifluxFetcher {
+    client: MinifluxClient,
+    categories: Vec<i64>,
+}
+
+impl MinifluxFetcher {
+    pub fn new(client: MinifluxClient, categories: Vec<i64>) -> Self {
+        MinifluxFetcher { client, categories }
+    }
+}
+
+#[async_trait]
+impl Fetcher for MinifluxFetcher {
+    async fn fetch(&self, limit: usize) -> Result<Vec<Entry>, CustomError> {
+        if let Err(e) = self.client.refresh_all_feeds().await {
+            error!("Error refreshing Miniflux feeds: {}", e);
+            if e.is_unauthorized() {
+                return Err(e.into());
+            }
+        }
+        match self.client.feed_counters().await {
+            Ok(counters) => info!("Unread entries in Miniflux: {}", counters.total_unread()),
+            Err(e) => debug!("Unable to get feed counters: {}", e),
+        }
+        if self.categories.is_empty() {
+            let entries = self.client.get_entries(limit).await?;
+            debug!(
+                "Entries: {:?}",
+                entries.iter().map(|entry| entry.id).collect::<Vec<_>>()
+            );
+            return Ok(entries);
+        }
+        let mut entries = Vec::new();
+        for category in self.categories.iter() {
+            if entries.len() >= limit {
+                break;
+            }
+            let query = EntryQuery::unread()
+                .limit(limit - entries.len())
+                .order(EntryOrder::PublishedAt)
+                .direction(Direction::Asc);
+            match self.client.get_category_entries_page(*category, &query).await {
+                Ok(page) => {
+                    debug!("Category {}: {} unread entries", category, page.total);
+                    entries.extend(page.entries);
+                }
+                Err(e) => error!("Error getting entries from category {}: {}", category, e),
+            }
+        }
+        Ok(entries)
+    }
+}
+
+// Replaces teasers with the full article scraped by Miniflux
+pub struct ContentEnricher {
+    fetcher: ContentFetcher,
+    client: MinifluxClient,
+}
+
+impl ContentEnricher {
+    pub fn new(fetcher: ContentFetcher, client: MinifluxClient) -> Self {
+        ContentEnricher { fetcher, client }
+    }
+}
+
+#[async_trait]
+impl Enricher for ContentEnricher {
+    async fn enrich(&self, entries: Vec<Entry>) -> Vec<Entry> {
+        self.fetcher.enrich(&self.client, entries).await
+    }
+}
+
+#[async_trait]
+impl Enricher for Normalizer {
+    async fn enrich(&self, mut entries: Vec<Entry>) -> Vec<Entry> {
+        for entry in entries.iter_mut() {
+            entry.content = self.normalize(&entry.content);
+        }
+        entries
+    }
+}
+
+#[async_trait]
+impl Summarizer for Model {
+    async fn summarize(&self, news: &[Value]) -> Result<Digest, CustomError> {
+        Model::summarize(self, news).await
+    }
+
+    fn overhead(&self) -> usize {
+        self.overhead_tokens()
+    }
+
+    fn fingerprint(&self) -> String {
+        Model::fingerprint(self)
+    }
+}
+
+#[async_trait]
+impl<S: Summarizer + ?Sized> Summarizer for Box<S> {
+    async fn summarize(&self, news: &[Value]) -> Result<Digest, CustomError> {
+        (**self).summarize(news).await
+    }
+
+    fn overhead(&self) -> usize {
+        (**self).overhead()
+    }
+
+    fn fingerprint(&self) -> String {
+        (**self).fingerprint()
+    }
+}
+
+// Splits the news in batches that fit a token budget, summarizes each one
+// and merges the partial digests, optionally asking the model to reduce them
+pub struct BatchSummarizer<S> {
+    inner: S,
+    budget: usize,
+    reduce: bool,
+}
+
+impl<S: Summarizer> BatchSummarizer<S> {
+    pub fn new(inner: S, budget: usize) -> Self {
+        BatchSummarizer {
+            inner,
+            budget,
+            reduce: false,
+        }
+    }
+
+    pub fn with_reduce(mut self, reduce: bool) -> Self {
+        self.reduce = reduce;
+        self
+    }
+}
+
+#[async_trait]
+impl<S: Summarizer> Summarizer for BatchSummarizer<S> {
+    async fn summarize(&self, news: &[Value]) -> Result<Digest, CustomError> {
+        let budget = self.budget.saturating_sub(self.inner.overhead());
+        let batches = batches(news, budget);
+        if batches.len() <= 1 {
+            return self.inner.summarize(batches.first().map(Vec::as_slice).unwrap_or_default()).await;
+        }
+        let mut digest = Digest::default();
+        for (position, batch) in batches.iter().enumerate() {
+            debug!("Summarizing batch {}/{} with {} news", position + 1, batches.len(), batch.len());
+            let partial = self.inner.summarize(batch).await?;
+            digest.failed.extend(partial.failed);
+            for item in partial.news {
+                if !digest.news.iter().any(|merged| merged.url == item.url) {
+                    digest.news.push(item);
+                }
+            }
+        }
+        if !self.reduce {
+            return Ok(digest);
+        }
+        let partials = digest
+            .news
+            .iter()
+            .map(serde_json::to_value)
+            .collect::<Result<Vec<_>, _>>()?;
+        if estimate_tokens(&Value::Array(partials.clone()).to_string()) > budget {
+            warn!("Partial digests exceed the token budget, skipping the reduce pass");
+            return Ok(digest);
+        }
+        match self.inner.summarize(&partials).await {
+            Ok(mut reduced) => {
+                // Their entries are marked as read anyway, so the partial
+                // summaries of the items the model left out are kept
+                for item in digest.news {
+                    if !reduced.news.iter().any(|kept| kept.url == item.url) {
+                        warn!("Reduce pass dropped {}, keeping its partial summary", item.url);
+                        reduced.news.push(item);
+                    }
+                }
+                reduced.failed = digest.failed;
+                Ok(reduced)
+            }
+            Err(e) => {
+                warn!("Reduce pass failed, keeping the merged digest: {}", e);
+                Ok(digest)
+            }
+        }
+    }
+
+    fn overhead(&self) -> usize {
+        self.inner.overhead()
+    }
+
+    fn fingerprint(&self) -> String {
+        self.inner.fingerprint()
+    }
+}
+
+// Groups the news greedily under the budget, truncating the content of
+// items too big for it on their own
+pub fn batches(news: &[Value], budget: usize) -> Vec<Vec<Value>> {
+    let mut batches = Vec::new();
+    let mut current = Vec::new();
+    let mut tokens = 0;
+    for item in news {
+        let item = fit(item, budget);
+        let size = estimate_tokens(&item.to_string());
+        if !current.is_empty() && tokens + size > budget {
+            batches.push(std::mem::take(&mut current));
+            tokens = 0;
+        }
+        tokens += size;
+        current.push(item);
+    }
+    if !current.is_empty() {
+        batches.push(current);
+    }
+    batches
+}
+
+// Shortens the `resume` of a news item until the item fits the budget
+fn fit(item: &Value, budget: usize) -> Value {
+    let mut item = item.clone();
+    loop {
+        let size = estimate_tokens(&item.to_string());
+        let Some(content) = item.get("resume").and_then(|content| content.as_str()) else {
+            return item;
+        };
+        let length = content.chars().count();
+        if size <= budget || length == 0 {
+            return item;
+        }
+        // Escaping makes the estimate inexact, shrink at least one char per try
+        let excess = ((size - budget) * CHARS_PER_TOKEN).max(1);
+        let truncated = truncate(content, length.saturating_sub(excess + 1));
+        // Nothing left but the ellipsis
+        item["resume"] = Value::String(if truncated.chars().count() >= length { String::new() } else { truncated });
+    }
+}
+
+// Summarizes each entry on its own, so a bad answer only delays that entry
+pub struct PerEntrySummarizer<S> {
+    inner: S,
+    concurrency: usize,
+    cache: Option<SummaryCache>,
+}
+
+impl<S: Summarizer> PerEntrySummarizer<S> {
+    pub fn new(inner: S, concurrency: usize) -> Self {
+        PerEntrySummarizer {
+            inner,
+            concurrency: concurrency.max(1),
+            cache: None,
+        }
+    }
+
+    pub fn with_cache(mut self, cache: SummaryCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    // The error is a string to keep the buffered stream Send
+    async fn summarize_entry(&self, item: &Value) -> Result<Digest, String> {
+        // Keyed by the whole news item, an edited title or content misses the cache
+        let content = item.to_string();
+        let model = self.inner.fingerprint();
+        let cache = self.cache.as_ref().zip(item.get("id").and_then(|id| id.as_i64()));
+        if let Some(digest) = cache.and_then(|(cache, id)| cache.get(id, &model, &content)) {
+            debug!("Cached summary for {}", item["url"]);
+            return Ok(digest);
+        }
+        let digest = self
+            .inner
+            .summarize(std::slice::from_ref(item))
+            .await
+            .map_err(|e| e.to_string())?;
+        if let Some((cache, id)) = cache {
+            if let Err(e) = cache.put(id, &model, &content, &digest) {
+                warn!("Unable to cache the summary of entry {}: {}", id, e);
+            }
+        }
+        Ok(digest)
+    }
+}
+
+#[async_trait]
+impl<S: Summarizer> Summarizer for PerEntrySummarizer<S> {
+    async fn summarize(&self, news: &[Value]) -> Result<Digest, CustomError> {
+        if let Some(Err(e)) = self.cache.as_ref().map(SummaryCache::prune) {
+            warn!("Unable to prune the summary cache: {}", e);
+        }
+        let results = stream::iter(news.to_vec())
+            .map(|item| async move { self.summarize_entry(&item).await })
+            .buffered(self.concurrency)
+            .collect::<Vec<_>>()
+            .await;
+        let mut digest = Digest::default();
+        let mut last_error = None;
+        for (item, result) in news.iter().zip(results) {
+            match result {
+                Ok(partial) => {
+                    digest.news.extend(partial.news);
+                    digest.failed.extend(partial.failed);
+                }
+                Err(e) => {
+                    error!("Error summarizing {}, it stays unread: {}", item["url"], e);
+                    digest.failed.extend(item.get("id").and_then(|id| id.as_i64()));
+                    last_error = Some(e);
+                }
+            }
+        }
+        match last_error {
+            // Nothing to send at all, let the entries wait for the next cycle
+            Some(e) if digest.news.is_empty() => Err(e.into()),
+            _ => Ok(digest),
+        }
+    }
+}
+
+#[async_trait]
+impl Acknowledger for MinifluxClient {
+    async fn acknowledge(&self, entry_ids: Vec<i64>) -> Result<(), CustomError> {
+        Ok(self.mark_as_read_some(entry_ids).await?)
+    }
+}
+
+// Maps a news item returned by the model back to the entry it summarizes
+fn find_entry<'a>(entries: &'a [Entry], url: &str) -> Option<&'a Entry> {
+    entries.iter().find(|entry| !url.is_empty() && entry.url == url)
+}
+
+// Lead image and podcast/video links of the entry the model item refers to
+fn media_links(entry: Option<&Entry>, enabled: bool) -> Vec<(String, String)> {
+    let Some(entry) = entry.filter(|_| enabled) else {
+        return Vec::new();
+    };
+    let mut links = Vec::new();
+    if let Some(image) = entry.lead_image() {
+        links.push(("Image".to_string(), image.url.clone()));
+    }
+    if let Some(media) = entry.media() {
+        let label = if media.is_audio() { "Listen" } else { "Watch" };
+        links.push((label.to_string(), media.url.clone()));
+    }
+    links
+}
+
+async fn send_items(notifier: &dyn Notifier, notifications: &[Notification]) -> Result<(), CustomError> {
+    for notification in notifications {
+        notifier.send_item(notification).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Capabilities, Markup};
+    use async_trait::async_trait;
+    use std::sync::{Arc, Mutex};
+
+    struct StaticFetcher(Vec<Entry>);
+
+    #[async_trait]
+    impl Fetcher for StaticFetcher {
+        async fn fetch(&self, limit: usize) -> Result<Vec<Entry>, CustomError> {
+            Ok(self.0.iter().take(limit).cloned().collect())
+        }
+    }
+
+    struct StaticSummarizer(&'static str);
+
+    #[async_trait]
+    impl Summarizer for StaticSummarizer {
+        async fn summarize(&self, news: &[Value]) -> Result<Digest, CustomError> {
+            assert!(!news.is_empty());
+            Ok(Digest::parse(self.0)?)
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct Recorder {
+        messages: Arc<Mutex<Vec<String>>>,
+        fail: bool,
+    }
+
+    #[async_trait]
+    impl Notifier for Recorder {
+        fn name(&self) -> &str {
+            "recorder"
+        }
+
+        fn capabilities(&self) -> Capabilities {
+            Capabilities {
+                max_length: 1000,
+                markup: Markup::Plain,
+                icons: false,
+            }
+        }
+
+        async fn render(&self, item: &Notification) -> String {
+            item.title.clone()
+        }
+
+        async fn send(&self, message: &str) -> Result<(), CustomError> {
+            if self.fail {
+                return Err("unavailable".into());
+            }
+            self.messages.lock().unwrap().push(message.to_string());
+            Ok(())
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct Acks {
+        ids: Arc<Mutex<Vec<i64>>>,
+        fail: bool,
+    }
+
+    #[async_trait]
+    impl Acknowledger for Acks {
+        async fn acknowledge(&self, entry_ids: Vec<i64>) -> Result<(), CustomError> {
+            if self.fail {
+                return Err("read only".into());
+            }
+            self.ids.lock().unwrap().extend(entry_ids);
+            Ok(())
+        }
+    }
+
+    fn entries() -> Vec<Entry> {
+        (1..=3)
+            .map(|id| Entry {
+                id,
+                url: format!("https://example.org/{}", id),
+                title: format!("Entry {}", id),
+                content: "<p>Body</p>".to_string(),
+                ..Entry::default()
+            })
+            .collect()
+    }
+
+    const DIGEST: &str = r#"{"news":[{"url":"https://example.org/1","title":"One","summary":"First"}]}"#;
+
+    #[tokio::test]
+    async fn test_pipeline_delivers_and_acknowledges() {
+        let recorder = Recorder::default();
+        let acks = Acks::default();
+        let mut pipeline = Pipeline::new(StaticFetcher(entries()), StaticSummarizer(DIGEST), acks.clone())
+            .with_enricher(Normalizer::default())
+            .with_notifier(recorder.clone())
+            .with_max_entries(2);
+
+        let report = pipeline.run_once().await.unwrap();
+        assert_eq!(report, RunReport { fetched: 2, attempted: 1, delivered: 1, acknowledged: 2 });
+        assert_eq!(*recorder.messages.lock().unwrap(), vec!["One".to_string()]);
+        assert_eq!(*acks.ids.lock().unwrap(), vec![1, 2]);
+        assert!(pipeline.pending().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_ack_policy() {
+        let acks = Acks::default();
+        let failing = Recorder { fail: true, ..Recorder::default() };
+        let mut any = Pipeline::new(StaticFetcher(entries()), StaticSummarizer(DIGEST), acks.clone())
+            .with_notifier(Recorder::default())
+            .with_notifier(failing.clone());
+        assert_eq!(any.run_once().await.unwrap().acknowledged, 3);
+
+        let mut all = Pipeline::new(StaticFetcher(entries()), StaticSummarizer(DIGEST), acks.clone())
+            .with_ack_policy(AckPolicy::All)
+            .with_notifier(Recorder::default())
+            .with_notifier(failing);
+        let report = all.run_once().await.unwrap();
+        assert_eq!((report.delivered, report.acknowledged), (1, 0));
+        assert_eq!(acks.ids.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_keeps_pending_acks() {
+        let acks = Acks { fail: true, ..Acks::default() };
+        let recorder = Recorder::default();
+        let mut pipeline = Pipeline::new(StaticFetcher(entries()), StaticSummarizer(DIGEST), acks)
+            .with_notifier(recorder.clone());
+
+        pipeline.run_once().await.unwrap();
+        assert_eq!(pipeline.pending(), vec![1, 2, 3]);
+        // Delivered entries are not sent twice while their ack is pending
+        let report = pipeline.run_once().await.unwrap();
+        assert_eq!(report.fetched, 0);
+        assert_eq!(recorder.messages.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_fetches_past_pending_acks() {
+        let acks = Acks { fail: true, ..Acks::default() };
+        let mut pipeline = Pipeline::new(StaticFetcher(entries()), StaticSummarizer(DIGEST), acks)
+            .with_notifier(Recorder::default())
+            .with_max_entries(2);
+
+        pipeline.run_once().await.unwrap();
+        assert_eq!(pipeline.pending(), vec![1, 2]);
+        let report = pipeline.run_once().await.unwrap();
+        assert_eq!(report.fetched, 1);
+        assert_eq!(pipeline.pending(), vec![1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_without_notifiers() {
+        let acks = Acks::default();
+        let mut pipeline = Pipeline::new(StaticFetcher(entries()), StaticSummarizer(DIGEST), acks.clone());
+        assert!(pipeline.run_once().await.is_err());
+        assert!(acks.ids.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_summarizer_error() {
+        struct Broken;
+
+        #[async_trait]
+        impl Summarizer for Broken {
+            async fn summarize(&self, _news: &[Value]) -> Result<Digest, CustomError> {
+                Err("model down".into())
+            }
+        }
+
+        let acks = Acks::default();
+        let mut pipeline = Pipeline::new(StaticFetcher(entries()), Broken, acks.clone())
+            .with_notifier(Recorder::default());
+        assert!(pipeline.run_once().await.is_err());
+        assert!(acks.ids.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_media_links() {
+        let entry: Entry = serde_json::from_str(
+            r#"{"id":1,"url":"https://example.org/episode","enclosures":[
+                {"id":1,"url":"https://example.org/cover.jpg","mime_type":"image/jpeg"},
+                {"id":2,"url":"https://example.org/episode.mp3","mime_type":"audio/mpeg"}
+            ]}"#,
+        )
+        .unwrap();
+        let entries = vec![entry];
+        let links = |url: &str, enabled: bool| media_links(find_entry(&entries, url), enabled);
+        assert_eq!(
+            links("https://example.org/episode", true),
+            vec![
+                ("Image".to_string(), "https://example.org/cover.jpg".to_string()),
+                ("Listen".to_string(), "https://example.org/episode.mp3".to_string())
+            ]
+        );
+        assert!(links("https://example.org/other", true).is_empty());
+        assert!(links("https://example.org/episode", false).is_empty());
+    }
+
+    // Answers with one item per news it receives, recording each call
+    #[derive(Clone, Default)]
+    struct Echo {
+        calls: Arc<Mutex<Vec<usize>>>,
+    }
+
+    #[async_trait]
+    impl Summarizer for Echo {
+        async fn summarize(&self, news: &[Value]) -> Result<Digest, CustomError> {
+            self.calls.lock().unwrap().push(news.len());
+            let news = news
+                .iter()
+                .map(|item| DigestItem {
+                    title: item["title"].as_str().unwrap_or("").to_string(),
+                    url: item["url"].as_str().unwrap_or("").to_string(),
+                    summary: "Summary".to_string(),
+                })
+                .collect();
+            Ok(Digest { news, ..Digest::default() })
+        }
+    }
+
+    fn news(count: usize) -> Vec<Value> {
+        (0..count)
+            .map(|i| json!({"id": i, "title": format!("News {}", i), "url": format!("https://example.org/{}", i), "resume": "x".repeat(100)}))
+            .collect()
+    }
+
+    #[test]
+    fn test_batches() {
+        let news = news(5);
+        let size = estimate_tokens(&news[0].to_string());
+        let sizes = |budget| batches(&news, budget).iter().map(Vec::len).collect::<Vec<_>>();
+        assert_eq!(sizes(size * 2), vec![2, 2, 1]);
+        assert_eq!(sizes(size * 10), vec![5]);
+        assert_eq!(sizes(1), vec![1, 1, 1, 1, 1]);
+        assert!(batches(&[], 10).is_empty());
+    }
+
+    #[test]
+    fn test_batches_truncate_oversized_items() {
+        let mut news = news(2);
+        news[1]["resume"] = json!("word ".repeat(500));
+        let size = estimate_tokens(&news[0].to_string());
+        let batches = batches(&news, size * 2);
+        assert_eq!(batches.iter().map(Vec::len).collect::<Vec<_>>(), vec![1, 1]);
+        let oversized = &batches[1][0];
+        assert!(estimate_tokens(&oversized.to_string()) <= size * 2);
+        assert!(oversized["resume"].as_str().unwrap().ends_with('…'));
+        assert_eq!(oversized["url"], "https://example.org/1");
+        // Nothing else to cut, the item goes as it is
+        assert_eq!(fit(&json!({"title": "t"}), 0), json!({"title": "t"}));
+    }
+
+    #[tokio::test]
+    async fn test_batch_summarizer_leaves_room_for_the_prompt() {
+        struct Verbose(Echo);
+
+        #[async_trait]
+        impl Summarizer for Verbose {
+            async fn summarize(&self, news: &[Value]) -> Result<Digest, CustomError> {
+                self.0.summarize(news).await
+            }
+
+            fn overhead(&self) -> usize {
+                1000
+            }
+        }
+
+        let news = news(4);
+        let size = estimate_tokens(&news[0].to_string());
+        let echo = Echo::default();
+        BatchSummarizer::new(Verbose(echo.clone()), 1000 + size * 2)
+            .summarize(&news)
+            .await
+            .unwrap();
+        assert_eq!(*echo.calls.lock().unwrap(), vec![2, 2]);
+    }
+
+    #[tokio::test]
+    async fn test_batch_summarizer_merges_partial_digests() {
+        let news = news(5);
+        let size = estimate_tokens(&news[0].to_string());
+        let echo = Echo::default();
+        let digest = BatchSummarizer::new(echo.clone(), size * 2).summarize(&news).await.unwrap();
+        assert_eq!(*echo.calls.lock().unwrap(), vec![2, 2, 1]);
+        assert_eq!(digest.news.len(), 5);
+        assert_eq!(digest.news[4].url, "https://example.org/4");
+    }
+
+    #[tokio::test]
+    async fn test_batch_summarizer_reduce_pass() {
+        let news = news(4);
+        let size = estimate_tokens(&news[0].to_string());
+        let echo = Echo::default();
+        let digest = BatchSummarizer::new(echo.clone(), size * 2)
+            .with_reduce(true)
+            .summarize(&news)
+            .await
+            .unwrap();
+        assert_eq!(*echo.calls.lock().unwrap(), vec![2, 2, 4]);
+        assert_eq!(digest.news.len(), 4);
+
+        let single = Echo::default();
+        BatchSummarizer::new(single.clone(), size * 10)
+            .with_reduce(true)
+            .summarize(&news)
+            .await
+            .unwrap();
+        assert_eq!(*single.calls.lock().unwrap(), vec![4]);
+    }
+
+    #[tokio::test]
+    async fn test_batch_summarizer_keeps_items_the_reduce_pass_dropped() {
+        // Reduces the partial digests to their first item
+        struct Picky(Echo);
+
+        #[async_trait]
+        impl Summarizer for Picky {
+            async fn summarize(&self, news: &[Value]) -> Result<Digest, CustomError> {
+                let mut digest = self.0.summarize(news).await?;
+                if news.len() > 2 {
+                    digest.news.truncate(1);
+                    digest.news[0].summary = "Reduced".to_string();
+                }
+                Ok(digest)
+            }
+        }
+
+        let news = news(4);
+        let size = estimate_tokens(&news[0].to_string());
+        let echo = Echo::default();
+        let digest = BatchSummarizer::new(Picky(echo.clone()), size * 2)
+            .with_reduce(true)
+            .summarize(&news)
+            .await
+            .unwrap();
+        assert_eq!(*echo.calls.lock().unwrap(), vec![2, 2, 4]);
+        let urls = digest.news.iter().map(|item| item.url.as_str()).collect::<Vec<_>>();
+        assert_eq!(
+            urls,
+            vec!["https://example.org/0", "https://example.org/1", "https://example.org/2", "https://example.org/3"]
+        );
+        assert_eq!(digest.news[0].summary, "Reduced");
+        assert_eq!(digest.news[1].summary, "Summary");
+    }
+
+    struct Flaky {
+        calls: Arc<Mutex<usize>>,
+    }
+
+    #[async_trait]
+    impl Summarizer for Flaky {
+        async fn summarize(&self, news: &[Value]) -> Result<Digest, CustomError> {
+            *self.calls.lock().unwrap() += 1;
+            assert_eq!(news.len(), 1);
+            if news[0]["title"] == "News 1" {
+                return Err("invalid answer".into());
+            }
+            Echo::default().summarize(news).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_per_entry_summarizer_skips_failed_entries() {
+        let calls = Arc::new(Mutex::new(0));
+        let summarizer = PerEntrySummarizer::new(Flaky { calls: calls.clone() }, 2);
+        let digest = summarizer.summarize(&news(3)).await.unwrap();
+        assert_eq!(*calls.lock().unwrap(), 3);
+        let urls = digest.news.iter().map(|item| item.url.as_str()).collect::<Vec<_>>();
+        assert_eq!(urls, vec!["https://example.org/0", "https://example.org/2"]);
+        assert_eq!(digest.failed, vec![1]);
+        assert!(summarizer.summarize(&news(3)[1..2]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_per_entry_summarizer_cache() {
+        let dir = std::env::temp_dir().join(format!("pipeline-summaries-{}", std::process::id()));
+        let mut news = news(2);
+        for (id, item) in news.iter_mut().enumerate() {
+            item["id"] = json!(id);
+        }
+        let echo = Echo::default();
+        let summarizer = PerEntrySummarizer::new(echo.clone(), 4).with_cache(SummaryCache::new(&dir));
+        assert_eq!(summarizer.summarize(&news).await.unwrap().news.len(), 2);
+        assert_eq!(summarizer.summarize(&news).await.unwrap().news.len(), 2);
+        assert_eq!(echo.calls.lock().unwrap().len(), 2);
+
+        news[1]["resume"] = json!("edited");
+        summarizer.summarize(&news).await.unwrap();
+        assert_eq!(*echo.calls.lock().unwrap(), vec![1, 1, 1]);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_keeps_failed_entries_unread() {
+        struct FailsOnSecond;
+
+        #[async_trait]
+        impl Summarizer for FailsOnSecond {
+            async fn summarize(&self, news: &[Value]) -> Result<Digest, CustomError> {
+                if news[0]["id"] == 2 {
+                    return Err("invalid answer".into());
+                }
+                Echo::default().summarize(news).await
+            }
+        }
+
+        let acks = Acks::default();
+        let recorder = Recorder::default();
+        let mut pipeline = Pipeline::new(StaticFetcher(entries()), PerEntrySummarizer::new(FailsOnSecond, 2), acks.clone())
+            .with_notifier(recorder.clone());
+
+        let report = pipeline.run_once().await.unwrap();
+        assert_eq!((report.fetched, report.acknowledged), (3, 2));
+        assert_eq!(*acks.ids.lock().unwrap(), vec![1, 3]);
+        assert!(pipeline.pending().is_empty());
+    }
+}